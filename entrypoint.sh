@@ -2,10 +2,9 @@
 
 # Check if the REGENERATE environment variable is set to "true"
 if [ "$REGENERATE" = "true" ]; then
-    # Execute /bin/server with --regenerate option, followed by normal execution
-    /bin/faster-elevation --regenerate
-    /bin/faster-elevation
+    # Synchronise the index with the data directory, then serve
+    exec /bin/faster-elevation serve --index
 else
-    # Execute /bin/server normally
-    /bin/faster-elevation
-fi
\ No newline at end of file
+    # Serve with the existing index
+    exec /bin/faster-elevation serve
+fi
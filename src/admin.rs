@@ -0,0 +1,308 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+    extract::{Multipart, Path, Request, State},
+    middleware::Next,
+};
+use axum_macros::debug_handler;
+use gdal::Dataset;
+use serde::Serialize;
+use std::path::{Component, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use crate::{AppState, error_response};
+use crate::ingest::sync_file;
+use crate::validate::validate_dataset;
+use crate::watch::invalidate_areas;
+
+/// File extensions accepted for uploads, compared in lower case.
+const UPLOAD_EXTENSIONS: [&str; 2] = ["tif", "tiff"];
+
+/// Structure representing the outcome of an upload or removal.
+#[derive(Serialize)]
+struct AdminResult {
+    /// Path of the file relative to the data directory.
+    path: String,
+    /// Number of geo_data rows whose footprint changed.
+    changed: usize,
+}
+
+/// Temporary file an upload is written to, removed when the upload ends without being stored.
+///
+/// Removing it on drop also covers clients that disconnect, which cancels the handler.
+struct PartialUpload(PathBuf);
+
+impl Drop for PartialUpload {
+    fn drop(&mut self) {
+        // After a successful rename there is nothing left to remove.
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Compares two tokens in constant time, so the response time does not reveal how much of a token matched.
+fn tokens_match(token: &str, admin_token: &str) -> bool {
+    token.len() == admin_token.len()
+        && token.bytes().zip(admin_token.bytes()).fold(0u8, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// Checks the bearer token of requests to the admin API.
+///
+/// Without `$ADMINTOKEN` the admin API is disabled and every request is rejected.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the admin token.
+/// * `request` - The incoming request.
+/// * `next` - The handler of the request.
+///
+/// # Returns
+/// The response of the handler, or 401 / 403 if the request is not authorised.
+pub async fn require_admin(State(appstate): State<AppState>, request: Request, next: Next) -> Response {
+    let admin_token = match &appstate.admin_token {
+        Some(admin_token) => admin_token,
+        None => return error_response(StatusCode::FORBIDDEN, "Admin API is disabled, set $ADMINTOKEN to enable it.".to_string())
+    };
+    let authorised = request.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| tokens_match(token, admin_token));
+    if !authorised {
+        return error_response(StatusCode::UNAUTHORIZED, "Missing or invalid admin token.".to_string());
+    }
+    next.run(request).await
+}
+
+/// Parses the target path of an upload relative to the data directory.
+///
+/// # Arguments
+/// * `path` - Requested path, a file with an upload extension or a directory the file name is appended to.
+/// * `file_name` - Name of the uploaded file.
+///
+/// # Returns
+/// * `Ok(PathBuf)` with the relative path of the uploaded file.
+/// * `Err(String)` if the path leaves the data directory or does not name a GeoTIFF.
+fn upload_path(path: Option<&str>, file_name: &str) -> Result<PathBuf, String> {
+    let mut target = PathBuf::from(path.unwrap_or(""));
+    if !target.components().all(|component| matches!(component, Component::Normal(_))) {
+        return Err("path must be relative to the data directory.".to_string());
+    }
+    let has_upload_extension = |path: &std::path::Path| path.extension()
+        .is_some_and(|extension| UPLOAD_EXTENSIONS.contains(&extension.to_string_lossy().to_lowercase().as_str()));
+    if !has_upload_extension(&target) {
+        let file_name = PathBuf::from(file_name);
+        match file_name.file_name() {
+            Some(name) => target.push(name),
+            None => return Err("Uploaded file has no name.".to_string())
+        };
+    }
+    if !has_upload_extension(&target) {
+        return Err("Only GeoTIFF files can be uploaded.".to_string());
+    }
+    Ok(target)
+}
+
+/// Checks that an uploaded file is a GeoTIFF that can be indexed.
+///
+/// # Arguments
+/// * `path` - Full path of the uploaded file.
+///
+/// # Returns
+/// * `Ok(())` if the file can be indexed.
+/// * `Err(String)` with the failed checks.
+fn validate_upload(path: &std::path::Path) -> Result<(), String> {
+    match Dataset::open(path) {
+        Ok(dataset) if dataset.driver().short_name() == "GTiff" => {},
+        _ => return Err("File is not a GeoTIFF.".to_string())
+    };
+    let errors = validate_dataset(path).errors();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join(" "))
+    }
+}
+
+/// Writes the file of an upload to a temporary file in the data directory.
+///
+/// # Arguments
+/// * `multipart` - The multipart body with a `file` field and an optional `path` field.
+/// * `temporary` - Full path of the temporary file.
+///
+/// # Returns
+/// * `Ok((Option<String>, String))` with the requested path and the name of the uploaded file.
+/// * `Err(String)` if the body is malformed or the file could not be written.
+async fn receive_upload(multipart: &mut Multipart, temporary: &std::path::Path) -> Result<(Option<String>, String), String> {
+    let mut path: Option<String> = None;
+    let mut file_name: Option<String> = None;
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return Err(format!("Multipart body could not be read: {}", e))
+        };
+        match field.name() {
+            Some("path") => match field.text().await {
+                Ok(text) => path = Some(text),
+                Err(e) => return Err(format!("path could not be read: {}", e))
+            },
+            Some("file") => {
+                file_name = Some(field.file_name().unwrap_or("").to_string());
+                let mut file = match tokio::fs::File::create(temporary).await {
+                    Ok(file) => file,
+                    Err(e) => return Err(format!("Upload could not be stored: {}", e))
+                };
+                loop {
+                    match field.chunk().await {
+                        Ok(Some(chunk)) => if let Err(e) = file.write_all(&chunk).await {
+                            return Err(format!("Upload could not be stored: {}", e));
+                        },
+                        Ok(None) => break,
+                        Err(e) => return Err(format!("Upload could not be read: {}", e))
+                    };
+                }
+                if let Err(e) = file.flush().await {
+                    return Err(format!("Upload could not be stored: {}", e));
+                }
+            },
+            _ => {}
+        };
+    }
+    match file_name {
+        Some(file_name) => Ok((path, file_name)),
+        None => Err("file is a required field.".to_string())
+    }
+}
+
+/// Handles POST requests uploading a GeoTIFF into the data directory.
+///
+/// The multipart body carries the GeoTIFF in the `file` field and optionally the target `path`
+/// relative to the data directory, which decides its layer and priority like any other file.
+/// The upload is validated before it replaces an existing file, then indexed and the cached
+/// lookups inside its footprint are invalidated.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection, data directory and cache.
+/// * `multipart` - The multipart body of the request.
+///
+/// # Returns
+/// 201 with the path of the stored file, or an error response.
+#[debug_handler]
+pub async fn post_dataset(State(appstate): State<AppState>, mut multipart: Multipart) -> Response {
+    let datadir = PathBuf::from(&appstate.datadir);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_nanos());
+    let temporary = PartialUpload(datadir.join(format!(".upload-{}.part", nanos)));
+    let (path, file_name) = match receive_upload(&mut multipart, &temporary.0).await {
+        Ok(upload) => upload,
+        Err(e) => {
+            return error_response(StatusCode::BAD_REQUEST, e);
+        }
+    };
+    let filepath = match upload_path(path.as_deref(), &file_name) {
+        Ok(filepath) => filepath,
+        Err(e) => {
+            return error_response(StatusCode::BAD_REQUEST, e);
+        }
+    };
+    let validation_path = temporary.0.clone();
+    let validated = tokio::task::spawn_blocking(move || validate_upload(&validation_path)).await;
+    match validated {
+        Ok(Ok(())) => {},
+        Ok(Err(e)) => {
+            return error_response(StatusCode::UNPROCESSABLE_ENTITY, e);
+        }
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string());
+        }
+    };
+    let target = datadir.join(&filepath);
+    if let Some(parent) = target.parent() {
+        if let Err(e) = tokio::fs::create_dir_all(parent).await {
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Directory could not be created: {}", e));
+        }
+    }
+    if let Err(e) = tokio::fs::rename(&temporary.0, &target).await {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Upload could not be stored: {}", e));
+    }
+    match sync_file(&datadir, &appstate.db_connection, &appstate.ingest_config, &filepath).await {
+        Ok(areas) => {
+            let changed = areas.len();
+            invalidate_areas(&appstate, areas);
+            (StatusCode::CREATED, Json(AdminResult {path: filepath.to_string_lossy().to_string(), changed})).into_response()
+        },
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Upload was stored but could not be indexed: {}", e))
+    }
+}
+
+/// Handles DELETE requests removing a dataset file from the data directory and the index.
+///
+/// Only datasets stored as a file of their own can be removed, datasets inside archives, VRTs
+/// or remote lists are removed by removing the file containing them.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection, data directory and cache.
+/// * `id` - Id of the dataset in geo_data.
+///
+/// # Returns
+/// 200 with the path of the removed file, or an error response.
+#[debug_handler]
+pub async fn delete_dataset(State(appstate): State<AppState>, Path(id): Path<i32>) -> Response {
+    let datadir = PathBuf::from(&appstate.datadir);
+    let row = match appstate.db_connection.get().await {
+        Ok(conn) => conn.query_opt("SELECT path, COALESCE(file_path, path) AS file_path FROM geo_data WHERE id = $1", &[&id]).await,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string());
+        }
+    };
+    let (path, file_path): (String, String) = match row {
+        Ok(Some(row)) => (row.get("path"), row.get("file_path")),
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, format!("No dataset with id {}.", id)),
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Dataset could not be read.".to_string());
+        }
+    };
+    if path != file_path {
+        return error_response(StatusCode::CONFLICT, format!("Dataset is part of {}, remove that file instead.", file_path));
+    }
+    let filepath = PathBuf::from(&file_path);
+    if let Err(e) = tokio::fs::remove_file(datadir.join(&filepath)).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("File could not be removed: {}", e));
+        }
+    }
+    match sync_file(&datadir, &appstate.db_connection, &appstate.ingest_config, &filepath).await {
+        Ok(areas) => {
+            let changed = areas.len();
+            invalidate_areas(&appstate, areas);
+            (StatusCode::OK, Json(AdminResult {path: file_path, changed})).into_response()
+        },
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("File was removed but the index could not be updated: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_tokens() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret1", "secret"));
+        assert!(!tokens_match("", "secret"));
+    }
+
+    #[test]
+    fn places_uploads_inside_the_data_directory() {
+        assert_eq!(upload_path(None, "N47E008.tif"), Ok(PathBuf::from("N47E008.tif")));
+        assert_eq!(upload_path(Some("lidar/2024"), "tile.TIFF"), Ok(PathBuf::from("lidar/2024/tile.TIFF")));
+        assert_eq!(upload_path(Some("lidar/tile.tif"), "upload.tif"), Ok(PathBuf::from("lidar/tile.tif")));
+        // Only the file name of the uploaded file is used.
+        assert_eq!(upload_path(Some("lidar"), "../../tile.tif"), Ok(PathBuf::from("lidar/tile.tif")));
+        assert!(upload_path(Some("../lidar"), "tile.tif").is_err());
+        assert!(upload_path(Some("/etc"), "tile.tif").is_err());
+        assert!(upload_path(Some("lidar"), "tile.png").is_err());
+    }
+}
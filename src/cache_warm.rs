@@ -0,0 +1,102 @@
+use serde_json::json;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use crate::LookupOptions;
+
+/// Outcome of warming the cache of a server.
+pub struct WarmReport {
+    /// Number of locations sent to the server.
+    pub requested: usize,
+    /// Number of locations the server answered with an error.
+    pub failed: usize,
+}
+
+/// Reads the locations of a warm list, one `latitude,longitude` pair or location identifier per line.
+///
+/// Empty lines and lines starting with `#` are ignored.
+///
+/// # Arguments
+/// * `path` - Path of the list, `-` reads from stdin.
+///
+/// # Returns
+/// * `Ok(Vec<serde_json::Value>)` with the locations in the format of the POST lookup endpoint.
+/// * `Err(String)` if the list could not be read.
+fn read_locations(path: &Path) -> Result<Vec<serde_json::Value>, String> {
+    let reader: Box<dyn BufRead> = if path == Path::new("-") {
+        Box::new(BufReader::new(std::io::stdin()))
+    } else {
+        match std::fs::File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => return Err(format!("{} could not be opened: {}", path.display(), e))
+        }
+    };
+    let mut locations = Vec::new();
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Err(format!("{} could not be read: {}", path.display(), e))
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let latlon = line.split(",").collect::<Vec<&str>>();
+        let location = match latlon.as_slice() {
+            [lat, lon] => match (lat.trim().parse::<f64>(), lon.trim().parse::<f64>()) {
+                (Ok(lat), Ok(lon)) => json!({"latitude": lat, "longitude": lon}),
+                _ => json!({"location": line})
+            },
+            _ => json!({"location": line})
+        };
+        locations.push(location);
+    }
+    Ok(locations)
+}
+
+/// Fills the lookup cache of a running server by looking up every location of a list.
+///
+/// The cache lives in the server process, so the locations are sent to its lookup endpoint
+/// in batches with the same options later requests will use.
+///
+/// # Arguments
+/// * `path` - Path of the location list, `-` reads from stdin.
+/// * `url` - URL of the lookup endpoint of the server.
+/// * `options` - Options of the lookups, they are part of the cache key.
+/// * `srs` - EPSG code of the coordinates in the list, EPSG 4326 if not set.
+/// * `batch_size` - Number of locations per request.
+///
+/// # Returns
+/// * `Ok(WarmReport)` with the number of requested and failed locations.
+/// * `Err(String)` if the list could not be read or the server not be reached.
+pub async fn warm_cache(path: &Path, url: &str, options: &LookupOptions, srs: Option<u32>, batch_size: usize) -> Result<WarmReport, String> {
+    let locations = read_locations(path)?;
+    let client = reqwest::Client::new();
+    let mut report = WarmReport {requested: 0, failed: 0};
+    for batch in locations.chunks(batch_size.max(1)) {
+        let body = json!({
+            "locations": batch,
+            "datum": options.datum.map(|datum| datum.as_str()),
+            "layers": options.layers,
+            "blend": options.blend,
+            "resolution": options.resolution,
+            "metadata": options.metadata,
+            "srs": srs,
+        });
+        let response = match client.post(url).json(&body).send().await {
+            Ok(response) => response,
+            Err(e) => return Err(format!("Server could not be reached: {}", e))
+        };
+        if !response.status().is_success() {
+            return Err(format!("Server answered with {}", response.status()));
+        }
+        let results: serde_json::Value = match response.json().await {
+            Ok(results) => results,
+            Err(e) => return Err(format!("Response could not be parsed: {}", e))
+        };
+        report.requested += batch.len();
+        report.failed += results["results"].as_array()
+            .map_or(batch.len(), |results| results.iter().filter(|result| !result["error"].is_null()).count());
+        println!("{} of {} locations looked up", report.requested, locations.len());
+    }
+    Ok(report)
+}
@@ -0,0 +1,218 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+    extract::{Path, Query, State},
+};
+use axum_macros::debug_handler;
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio_postgres::Row;
+use tokio_postgres::types::ToSql;
+use crate::{AppState, error_response};
+
+/// Number of datasets per page when the request does not specify it.
+const DEFAULT_LIMIT: i64 = 100;
+/// Upper bound for the number of datasets per page.
+const MAX_LIMIT: i64 = 1000;
+/// Columns selected for a dataset of the catalog.
+const DATASET_COLUMNS: &str = "id, path, file_path, layer, horizontal_crs, file_size, resolution, ground_resolution, \
+    vertical_datum, vertical_crs, vertical_accuracy, priority, dataset_name, acquisition_date, modified, ST_AsGeoJSON(object) AS footprint";
+
+/// Structure representing an indexed dataset in the catalog list.
+#[derive(Serialize)]
+struct DatasetSummary {
+    id: i32,
+    /// Dataset path relative to the data directory.
+    path: String,
+    /// Path of the file containing the dataset relative to the data directory.
+    file_path: Option<String>,
+    layer: Option<String>,
+    /// Horizontal coordinate system of the dataset.
+    crs: Option<String>,
+    /// Size of the file containing the dataset in bytes.
+    file_size: Option<i64>,
+    /// Resolution as stored by the legacy index.
+    resolution: Option<i32>,
+    /// Ground resolution in metres.
+    ground_resolution: Option<f64>,
+    /// Footprint in EPSG 4326 as GeoJSON geometry.
+    footprint: Option<serde_json::Value>,
+}
+
+impl DatasetSummary {
+    /// Reads a dataset from a row selected with `DATASET_COLUMNS`.
+    fn from_row(row: &Row) -> DatasetSummary {
+        let footprint: Option<String> = row.get("footprint");
+        DatasetSummary {
+            id: row.get("id"),
+            path: row.get("path"),
+            file_path: row.get("file_path"),
+            layer: row.get("layer"),
+            crs: row.get("horizontal_crs"),
+            file_size: row.get("file_size"),
+            resolution: row.get("resolution"),
+            ground_resolution: row.get("ground_resolution"),
+            footprint: footprint.and_then(|footprint| serde_json::from_str(&footprint).ok()),
+        }
+    }
+}
+
+/// Structure representing an indexed dataset with everything stored about it.
+#[derive(Serialize)]
+struct DatasetDetail {
+    #[serde(flatten)]
+    summary: DatasetSummary,
+    vertical_datum: Option<String>,
+    vertical_crs: Option<String>,
+    /// Stated vertical accuracy in metres.
+    vertical_accuracy: Option<f64>,
+    priority: Option<i32>,
+    dataset_name: Option<String>,
+    acquisition_date: Option<String>,
+    /// Modification time of the file in seconds since the Unix epoch.
+    modified: Option<i64>,
+}
+
+impl DatasetDetail {
+    /// Reads a dataset from a row selected with `DATASET_COLUMNS`.
+    fn from_row(row: &Row) -> DatasetDetail {
+        DatasetDetail {
+            summary: DatasetSummary::from_row(row),
+            vertical_datum: row.get("vertical_datum"),
+            vertical_crs: row.get("vertical_crs"),
+            vertical_accuracy: row.get("vertical_accuracy"),
+            priority: row.get("priority"),
+            dataset_name: row.get("dataset_name"),
+            acquisition_date: row.get("acquisition_date"),
+            modified: row.get("modified"),
+        }
+    }
+}
+
+/// Structure representing a page of the catalog.
+#[derive(Serialize)]
+struct DatasetList {
+    /// Number of datasets matching the filter on all pages.
+    total: i64,
+    offset: i64,
+    limit: i64,
+    datasets: Vec<DatasetSummary>,
+}
+
+/// Parses an optional non-negative integer query parameter.
+fn integer_param(params: &HashMap<String, String>, name: &str, default: i64) -> Result<i64, String> {
+    match params.get(name) {
+        Some(value) => match value.parse::<i64>() {
+            Ok(value) if value >= 0 => Ok(value),
+            _ => Err(format!("Bad parameter format {}.", value))
+        },
+        None => Ok(default)
+    }
+}
+
+/// Handles GET requests listing the indexed datasets.
+///
+/// Query parameters:
+/// * `bbox` - Only datasets intersecting `min_lon,min_lat,max_lon,max_lat` in EPSG 4326 are listed.
+/// * `layer` - Only datasets of this layer are listed.
+/// * `offset` - Number of datasets skipped.
+/// * `limit` - Number of datasets per page.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection.
+/// * `params` - Query parameters of the request.
+///
+/// # Returns
+/// A page of datasets ordered by id.
+#[debug_handler]
+pub async fn get_datasets(
+    State(appstate): State<AppState>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let offset = match integer_param(&params, "offset", 0) {
+        Ok(offset) => offset,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e)
+    };
+    let limit = match integer_param(&params, "limit", DEFAULT_LIMIT) {
+        Ok(limit) if (1..=MAX_LIMIT).contains(&limit) => limit,
+        Ok(_) => return error_response(StatusCode::BAD_REQUEST, format!("limit must be between 1 and {}.", MAX_LIMIT)),
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e)
+    };
+    let bbox: Option<Vec<f64>> = match params.get("bbox") {
+        Some(bbox_string) => match bbox_string.split(",").map(|value| value.parse::<f64>()).collect::<Result<Vec<f64>, _>>() {
+            Ok(bbox) if bbox.len() == 4 => Some(bbox),
+            _ => return error_response(StatusCode::BAD_REQUEST, format!("Bad parameter format {}.", bbox_string))
+        },
+        None => None
+    };
+    let layer = params.get("layer");
+
+    let mut conditions: Vec<String> = Vec::new();
+    let mut filter_params: Vec<&(dyn ToSql + Sync)> = Vec::new();
+    if let Some(bbox) = &bbox {
+        conditions.push(format!("object && ST_MakeEnvelope(${}, ${}, ${}, ${}, 4326)", filter_params.len() + 1, filter_params.len() + 2, filter_params.len() + 3, filter_params.len() + 4));
+        filter_params.extend_from_slice(&[&bbox[0], &bbox[1], &bbox[2], &bbox[3]]);
+    }
+    if let Some(layer) = layer {
+        conditions.push(format!("layer = ${}", filter_params.len() + 1));
+        filter_params.push(layer);
+    }
+    let filter = if conditions.is_empty() { String::new() } else { format!(" WHERE {}", conditions.join(" AND ")) };
+
+    let conn = match appstate.db_connection.get().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string());
+        }
+    };
+    let total: i64 = match conn.query_one(&format!("SELECT COUNT(*) FROM geo_data{}", filter), &filter_params).await {
+        Ok(row) => row.get(0),
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Datasets could not be listed.".to_string());
+        }
+    };
+    let mut page_params = filter_params.clone();
+    page_params.extend_from_slice(&[&limit, &offset]);
+    let query = format!("SELECT {} FROM geo_data{} ORDER BY id LIMIT ${} OFFSET ${}", DATASET_COLUMNS, filter, filter_params.len() + 1, filter_params.len() + 2);
+    let rows = match conn.query(&query, &page_params).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Datasets could not be listed.".to_string());
+        }
+    };
+    let datasets = rows.iter().map(DatasetSummary::from_row).collect();
+    (StatusCode::OK, Json(DatasetList {total, offset, limit, datasets})).into_response()
+}
+
+/// Handles GET requests for a single indexed dataset.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection.
+/// * `id` - Id of the dataset in geo_data.
+///
+/// # Returns
+/// The dataset with everything stored about it, or 404 if it is not indexed.
+#[debug_handler]
+pub async fn get_dataset(
+    State(appstate): State<AppState>, Path(id): Path<i32>) -> Response {
+    let conn = match appstate.db_connection.get().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string());
+        }
+    };
+    let row = match conn.query_opt(&format!("SELECT {} FROM geo_data WHERE id = $1", DATASET_COLUMNS), &[&id]).await {
+        Ok(row) => row,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Dataset could not be read.".to_string());
+        }
+    };
+    match row {
+        Some(row) => (StatusCode::OK, Json(DatasetDetail::from_row(&row))).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, format!("No dataset with id {}.", id))
+    }
+}
@@ -0,0 +1,197 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+    extract::{Query, State},
+};
+use axum_macros::debug_handler;
+use serde_json::json;
+use std::collections::HashMap;
+use crate::{AppState, ConnectionPool, error_response};
+
+/// Upper bounds in metres of the ground resolution classes used when the request does not specify them.
+const DEFAULT_RESOLUTION_CLASSES: [f64; 5] = [1.0, 5.0, 10.0, 30.0, 100.0];
+/// Highest zoom level the coverage is simplified for.
+pub const MAX_ZOOM: u32 = 24;
+/// Size in pixels of a web map tile.
+const TILE_SIZE: f64 = 256.0;
+
+/// Returns the simplification tolerance in degrees matching one tile pixel at a zoom level.
+pub fn zoom_tolerance(zoom: u32) -> f64 {
+    360f64 / (TILE_SIZE * 2f64.powi(zoom as i32))
+}
+
+/// Returns the bounds of a ground resolution class as returned by `width_bucket`.
+///
+/// # Arguments
+/// * `resolution_class` - Index of the class, `0` below the first bound.
+/// * `resolution_classes` - Ascending upper bounds of the classes.
+///
+/// # Returns
+/// The lower and upper bound of the class in metres, `None` where the class is open.
+fn class_bounds(resolution_class: i32, resolution_classes: &[f64]) -> (Option<f64>, Option<f64>) {
+    let index = resolution_class as usize;
+    let min = if index == 0 { None } else { resolution_classes.get(index - 1).copied() };
+    (min, resolution_classes.get(index).copied())
+}
+
+/// Options of a coverage query.
+pub struct CoverageOptions {
+    /// Only datasets of this layer are considered.
+    pub layer: Option<String>,
+    /// Whether one feature is returned per layer.
+    pub per_layer: bool,
+    /// Whether one feature is returned per ground resolution class.
+    pub per_resolution: bool,
+    /// Ascending upper bounds in metres of the ground resolution classes.
+    pub resolution_classes: Vec<f64>,
+    /// Simplification tolerance in degrees.
+    pub tolerance: f64,
+}
+
+impl Default for CoverageOptions {
+    fn default() -> CoverageOptions {
+        CoverageOptions {layer: None, per_layer: false, per_resolution: false, resolution_classes: DEFAULT_RESOLUTION_CLASSES.to_vec(), tolerance: 0f64}
+    }
+}
+
+impl CoverageOptions {
+    /// Sets the grouping from a comma separated list of `layer` and `resolution`.
+    ///
+    /// # Returns
+    /// * `Ok(())` if all groups are known.
+    /// * `Err(String)` naming the first unknown group.
+    pub fn set_groups(&mut self, group_by: &str) -> Result<(), String> {
+        for group in group_by.split(",").filter(|group| !group.is_empty()) {
+            match group {
+                "layer" => self.per_layer = true,
+                "resolution" => self.per_resolution = true,
+                _ => return Err(format!("Unknown group {}.", group))
+            };
+        }
+        Ok(())
+    }
+
+    /// Sets the upper bounds of the ground resolution classes.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the bounds are finite and strictly ascending.
+    /// * `Err(String)` otherwise.
+    pub fn set_classes(&mut self, resolution_classes: Vec<f64>) -> Result<(), String> {
+        if resolution_classes.is_empty() || !resolution_classes.iter().all(|class| class.is_finite())
+            || !resolution_classes.windows(2).all(|pair| pair[0] < pair[1]) {
+            return Err("classes must be strictly ascending numbers.".to_string());
+        }
+        self.resolution_classes = resolution_classes;
+        Ok(())
+    }
+}
+
+/// Computes the union of the footprints of every group of indexed datasets.
+///
+/// # Arguments
+/// * `pool` - The connection pool to the PostgreSQL database.
+/// * `options` - Options of the coverage query.
+///
+/// # Returns
+/// * `Ok(serde_json::Value)` with a GeoJSON FeatureCollection holding one feature per group.
+/// * `Err(String)` if the coverage could not be computed.
+pub async fn query_coverage(pool: &ConnectionPool, options: &CoverageOptions) -> Result<serde_json::Value, String> {
+    let conn = match pool.get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(format!("Database Connection could not be established: {}", e))
+    };
+    // Without grouping the group columns are NULL, which puts all rows into one group.
+    let query = "SELECT CASE WHEN $4 THEN layer END AS layer, \
+        CASE WHEN $5 THEN width_bucket(ground_resolution, $2::float8[]) END AS resolution_class, \
+        ST_AsGeoJSON(ST_SimplifyPreserveTopology(ST_Union(object), $1)) AS geometry \
+        FROM geo_data WHERE ($3::varchar IS NULL OR layer = $3) GROUP BY 1, 2 ORDER BY 1, 2";
+    let rows = match conn.query(query, &[&options.tolerance, &options.resolution_classes, &options.layer, &options.per_layer, &options.per_resolution]).await {
+        Ok(rows) => rows,
+        Err(e) => return Err(format!("Coverage could not be computed: {}", e))
+    };
+
+    let mut features: Vec<serde_json::Value> = Vec::with_capacity(rows.len());
+    for row in rows {
+        let geometry: Option<String> = row.get("geometry");
+        let geometry: serde_json::Value = match geometry.as_deref().map(serde_json::from_str) {
+            Some(Ok(geometry)) => geometry,
+            Some(Err(e)) => return Err(format!("Coverage geometry could not be parsed: {}", e)),
+            None => continue
+        };
+        let mut properties = serde_json::Map::new();
+        if options.per_layer {
+            properties.insert("layer".to_string(), json!(row.get::<_, Option<String>>("layer")));
+        }
+        if options.per_resolution {
+            let (resolution_min, resolution_max) = match row.get::<_, Option<i32>>("resolution_class") {
+                Some(resolution_class) => class_bounds(resolution_class, &options.resolution_classes),
+                None => (None, None)
+            };
+            properties.insert("resolution_min".to_string(), json!(resolution_min));
+            properties.insert("resolution_max".to_string(), json!(resolution_max));
+        }
+        features.push(json!({"type": "Feature", "geometry": geometry, "properties": properties}));
+    }
+    Ok(json!({"type": "FeatureCollection", "features": features}))
+}
+
+/// Handles GET requests for the area covered by indexed datasets.
+///
+/// Query parameters:
+/// * `layer` - Only datasets of this layer are considered.
+/// * `group_by` - Comma separated list of `layer` and `resolution`, one feature is returned per group.
+/// * `classes` - Comma separated ascending upper bounds in metres of the ground resolution classes.
+/// * `zoom` - Web map zoom level the footprints are simplified for, unsimplified if not set.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection.
+/// * `params` - Query parameters of the request.
+///
+/// # Returns
+/// A GeoJSON FeatureCollection with the union of the footprints of every group.
+#[debug_handler]
+pub async fn get_coverage(
+    State(appstate): State<AppState>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let mut options = CoverageOptions {layer: params.get("layer").cloned(), ..CoverageOptions::default()};
+    if let Some(group_by) = params.get("group_by") {
+        if let Err(e) = options.set_groups(group_by) {
+            return error_response(StatusCode::BAD_REQUEST, e);
+        }
+    }
+    if let Some(classes_string) = params.get("classes") {
+        let resolution_classes = match classes_string.split(",").map(|class| class.parse::<f64>()).collect::<Result<Vec<f64>, _>>() {
+            Ok(resolution_classes) => resolution_classes,
+            Err(_) => return error_response(StatusCode::BAD_REQUEST, format!("Bad parameter format {}.", classes_string))
+        };
+        if let Err(e) = options.set_classes(resolution_classes) {
+            return error_response(StatusCode::BAD_REQUEST, e);
+        }
+    }
+    if let Some(zoom_string) = params.get("zoom") {
+        options.tolerance = match zoom_string.parse::<u32>() {
+            Ok(zoom) if zoom <= MAX_ZOOM => zoom_tolerance(zoom),
+            _ => return error_response(StatusCode::BAD_REQUEST, format!("zoom must be between 0 and {}.", MAX_ZOOM))
+        };
+    }
+    match query_coverage(&appstate.db_connection, &options).await {
+        Ok(coverage) => (StatusCode::OK, Json(coverage)).into_response(),
+        Err(e) => {
+            eprintln!("{}", e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "Coverage could not be computed.".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn class_bounds_follow_width_bucket() {
+        let resolution_classes = [1f64, 5f64, 10f64];
+        assert_eq!(class_bounds(0, &resolution_classes), (None, Some(1f64)));
+        assert_eq!(class_bounds(1, &resolution_classes), (Some(1f64), Some(5f64)));
+        assert_eq!(class_bounds(3, &resolution_classes), (Some(10f64), None));
+    }
+}
@@ -0,0 +1,243 @@
+use gdal::{Dataset, GeoTransform, GeoTransformEx};
+use serde::Deserialize;
+use std::env;
+use std::sync::Arc;
+use crate::raster::is_no_data;
+
+/// Vertical datum an elevation refers to.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerticalDatum {
+    /// EGM96 geoid, used by SRTM.
+    Egm96,
+    /// EGM2008 geoid, used by Copernicus DEM.
+    Egm2008,
+    /// WGS84 ellipsoid, used by GPS.
+    Ellipsoid,
+}
+
+impl VerticalDatum {
+    /// Returns the name of the datum as stored in geo_data and accepted by the API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerticalDatum::Egm96 => "egm96",
+            VerticalDatum::Egm2008 => "egm2008",
+            VerticalDatum::Ellipsoid => "ellipsoid",
+        }
+    }
+
+    /// Parses the name of a datum, ignoring case.
+    pub fn from_name(name: &str) -> Option<VerticalDatum> {
+        match name.to_lowercase().as_str() {
+            "egm96" => Some(VerticalDatum::Egm96),
+            "egm2008" => Some(VerticalDatum::Egm2008),
+            "ellipsoid" => Some(VerticalDatum::Ellipsoid),
+            _ => None,
+        }
+    }
+}
+
+/// Detects the vertical datum from the coordinate system of a dataset.
+///
+/// # Arguments
+/// * `projection` - WKT of the dataset coordinate system.
+///
+/// # Returns
+/// The datum if the coordinate system is compound with a known geoid, `None` otherwise.
+pub fn detect_vertical_datum(projection: &str) -> Option<VerticalDatum> {
+    let projection = projection.to_uppercase();
+    if projection.contains("EGM2008") || projection.contains("EGM08") {
+        Some(VerticalDatum::Egm2008)
+    } else if projection.contains("EGM96") {
+        Some(VerticalDatum::Egm96)
+    } else {
+        None
+    }
+}
+
+/// Geoid grid of one model, read into memory once so conversions do not touch the file.
+struct GeoidGrid {
+    /// Undulations row by row.
+    values: Vec<f32>,
+    width: usize,
+    height: usize,
+    /// Inverted geotransform, mapping longitude and latitude to pixel coordinates.
+    inverse: GeoTransform,
+}
+
+impl GeoidGrid {
+    /// Reads a geoid grid in geographic coordinates.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the grid, e.g. `egm96_15.gtx`.
+    ///
+    /// # Returns
+    /// * `Ok(GeoidGrid)` with the undulations of the first band.
+    /// * `Err(String)` if the grid could not be read or is not geographic.
+    fn open(path: &str) -> Result<GeoidGrid, String> {
+        let dataset = match Dataset::open(path) {
+            Ok(dataset) => dataset,
+            Err(e) => return Err(format!("Geoid grid {} could not be opened: {}", path, e))
+        };
+        if !dataset.spatial_ref().is_ok_and(|spatial_ref| spatial_ref.is_geographic()) {
+            return Err(format!("Geoid grid {} is not in geographic coordinates", path));
+        }
+        let (width, height) = dataset.raster_size();
+        if width < 2 || height < 2 {
+            return Err(format!("Geoid grid {} needs at least 2 x 2 pixels", path));
+        }
+        let inverse = match dataset.geo_transform().and_then(|geotransform| geotransform.invert()) {
+            Ok(inverse) => inverse,
+            Err(e) => return Err(format!("Geo transform of {} could not be inverted: {}", path, e))
+        };
+        let rasterband = match dataset.rasterband(1) {
+            Ok(rasterband) => rasterband,
+            Err(e) => return Err(format!("Rasterband of {} could not be opened: {}", path, e))
+        };
+        let no_data = rasterband.no_data_value();
+        let buffer = match rasterband.read_as::<f32>((0, 0), (width, height), (width, height), None) {
+            Ok(buffer) => buffer,
+            Err(e) => return Err(format!("Geoid grid {} could not be read: {}", path, e))
+        };
+        // NoData is stored as NaN, so it spreads into the interpolation instead of a bogus value.
+        let values = buffer.data().iter().map(|value| if is_no_data(*value as f64, no_data) { f32::NAN } else { *value }).collect();
+        Ok(GeoidGrid {values, width, height, inverse})
+    }
+
+    /// Returns the bilinearly interpolated value of the grid at a point.
+    ///
+    /// # Arguments
+    /// * `lat` - Latitude of the point.
+    /// * `lon` - Longitude of the point, grids from 0 to 360 degrees are handled as well.
+    ///
+    /// # Returns
+    /// * `Ok(f64)` with the geoid undulation at the point.
+    /// * `Err(String)` if the point lies outside the grid or next to NoData.
+    fn sample(&self, lat: f64, lon: f64) -> Result<f64, String> {
+        let (width, height) = (self.width as f64, self.height as f64);
+        let pixel = [lon, lon + 360f64, lon - 360f64].iter()
+            .map(|lon| self.inverse.apply(*lon, lat))
+            .find(|(pixel_x, pixel_y)| (0f64..=width).contains(pixel_x) && (0f64..=height).contains(pixel_y));
+        let (pixel_x, pixel_y) = match pixel {
+            Some(pixel) => pixel,
+            None => return Err(format!("{} {} lies outside the geoid grid", lat, lon))
+        };
+        // Interpolate between the four pixel centres surrounding the point. Within half a pixel of
+        // the edge of the grid the outermost pixels are used.
+        let left = (pixel_x - 0.5).floor().clamp(0f64, width - 2f64);
+        let top = (pixel_y - 0.5).floor().clamp(0f64, height - 2f64);
+        let weight_x = (pixel_x - 0.5 - left).clamp(0f64, 1f64);
+        let weight_y = (pixel_y - 0.5 - top).clamp(0f64, 1f64);
+        let value = |col: f64, row: f64| self.values[row as usize * self.width + col as usize] as f64;
+        let upper = value(left, top) * (1f64 - weight_x) + value(left + 1f64, top) * weight_x;
+        let lower = value(left, top + 1f64) * (1f64 - weight_x) + value(left + 1f64, top + 1f64) * weight_x;
+        let undulation = upper * (1f64 - weight_y) + lower * weight_y;
+        if undulation.is_nan() {
+            return Err(format!("Geoid grid has no data at {} {}", lat, lon));
+        }
+        Ok(undulation)
+    }
+}
+
+/// Local geoid grids used for datum conversion.
+#[derive(Clone, Default)]
+pub struct GeoidModels {
+    /// Geoid grid of EGM96, read from `$GEOIDEGM96`.
+    egm96: Option<Arc<GeoidGrid>>,
+    /// Geoid grid of EGM2008, read from `$GEOIDEGM2008`.
+    egm2008: Option<Arc<GeoidGrid>>,
+}
+
+impl GeoidModels {
+    /// Reads the geoid grids configured in the environment. Both grids are optional.
+    ///
+    /// # Returns
+    /// * `Ok(GeoidModels)` with the configured grids in memory.
+    /// * `Err(String)` if a configured grid could not be read.
+    pub fn from_env() -> Result<GeoidModels, String> {
+        let grid = |variable: &str| match env::var(variable) {
+            Ok(path) => GeoidGrid::open(&path).map(|grid| Some(Arc::new(grid))),
+            Err(_) => Ok(None)
+        };
+        Ok(GeoidModels {egm96: grid("GEOIDEGM96")?, egm2008: grid("GEOIDEGM2008")?})
+    }
+
+    /// Returns the geoid undulation (height of the geoid above the ellipsoid) at a point.
+    ///
+    /// # Arguments
+    /// * `datum` - The datum whose geoid is sampled.
+    /// * `lat` - Latitude of the point.
+    /// * `lon` - Longitude of the point.
+    ///
+    /// # Returns
+    /// * `Ok(f64)` with the bilinearly interpolated undulation, `0` for the ellipsoid.
+    /// * `Err(String)` if no grid is configured for the datum or the point lies outside of it.
+    pub fn undulation(&self, datum: VerticalDatum, lat: f64, lon: f64) -> Result<f64, String> {
+        let grid = match datum {
+            VerticalDatum::Ellipsoid => return Ok(0f64),
+            VerticalDatum::Egm96 => &self.egm96,
+            VerticalDatum::Egm2008 => &self.egm2008,
+        };
+        match grid {
+            Some(grid) => grid.sample(lat, lon),
+            None => Err(format!("No geoid grid configured for {}", datum.as_str()))
+        }
+    }
+
+    /// Returns whether the grids needed to convert heights between two datums are configured.
+    pub fn can_convert(&self, from: VerticalDatum, to: VerticalDatum) -> bool {
+        let available = |datum: VerticalDatum| match datum {
+            VerticalDatum::Ellipsoid => true,
+            VerticalDatum::Egm96 => self.egm96.is_some(),
+            VerticalDatum::Egm2008 => self.egm2008.is_some(),
+        };
+        from == to || (available(from) && available(to))
+    }
+
+    /// Converts a height at a point from one vertical datum into another.
+    ///
+    /// # Arguments
+    /// * `height` - The height in the source datum.
+    /// * `lat` - Latitude of the point.
+    /// * `lon` - Longitude of the point.
+    /// * `from` - Datum of `height`.
+    /// * `to` - Datum of the result.
+    ///
+    /// # Returns
+    /// * `Ok(f64)` with the height in the target datum.
+    /// * `Err(String)` if a required geoid grid is missing or does not cover the point.
+    pub fn convert(&self, height: f64, lat: f64, lon: f64, from: VerticalDatum, to: VerticalDatum) -> Result<f64, String> {
+        if from == to {
+            return Ok(height);
+        }
+        let ellipsoidal_height = height + self.undulation(from, lat, lon)?;
+        Ok(ellipsoidal_height - self.undulation(to, lat, lon)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Geoid with 3 x 2 pixels from 10 to 13 degrees east and from 50 to 48 degrees north.
+    fn geoids() -> GeoidModels {
+        let grid = GeoidGrid {values: vec![0f32, 1f32, 2f32, 3f32, 4f32, 5f32], width: 3, height: 2, inverse: [-10f64, 1f64, 0f64, 50f64, 0f64, -1f64]};
+        GeoidModels {egm96: Some(Arc::new(grid)), egm2008: None}
+    }
+
+    #[test]
+    fn interpolates_between_pixel_centres() {
+        let geoids = geoids();
+        assert_eq!(geoids.undulation(VerticalDatum::Egm96, 49f64, 11.5), Ok(2.5));
+        assert_eq!(geoids.undulation(VerticalDatum::Egm96, 49f64, -348.5), Ok(2.5));
+        assert_eq!(geoids.convert(100f64, 49f64, 11.5, VerticalDatum::Egm96, VerticalDatum::Ellipsoid), Ok(102.5));
+    }
+
+    #[test]
+    fn rejects_points_outside_the_grid() {
+        let geoids = geoids();
+        assert!(geoids.undulation(VerticalDatum::Egm96, 49f64, 14f64).is_err());
+        assert!(geoids.undulation(VerticalDatum::Egm96, 51f64, 11f64).is_err());
+        assert!(geoids.convert(100f64, 49f64, 11.5, VerticalDatum::Egm96, VerticalDatum::Egm2008).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use gdal::spatial_ref::CoordTransform;
+use crate::raster::epsg_spatial_ref;
+
+/// Characters of a geohash, each encoding five bits.
+const GEOHASH_ALPHABET: &str = "0123456789bcdefghjkmnpqrstuvwxyz";
+/// Characters of an Open Location Code, each encoding one base 20 digit.
+const PLUS_CODE_ALPHABET: &str = "23456789CFGHJMPQRVWX";
+/// Position of the separator in a full Open Location Code.
+const PLUS_CODE_SEPARATOR_POSITION: usize = 8;
+/// MGRS latitude band letters from 80°S northwards, 8° each.
+const MGRS_BANDS: &str = "CDEFGHJKLMNPQRSTUVWX";
+/// MGRS 100 km row letters.
+const MGRS_ROWS: &str = "ABCDEFGHJKLMNPQRSTUV";
+/// MGRS 100 km column letters of the three column sets.
+const MGRS_COLUMNS: [&str; 3] = ["ABCDEFGH", "JKLMNPQR", "STUVWXYZ"];
+
+/// Decodes a location identifier into the latitude and longitude of its cell centre.
+///
+/// MGRS grid references, Open Location Codes and geohashes are recognised in that order.
+///
+/// # Arguments
+/// * `location` - The location identifier.
+///
+/// # Returns
+/// * `Ok((f64, f64))` with latitude and longitude in EPSG 4326.
+/// * `Err(String)` if the identifier is not valid in any of the encodings.
+pub fn decode_location(location: &str) -> Result<(f64, f64), String> {
+    let location = location.trim();
+    if location.contains('+') {
+        return decode_plus_code(location);
+    }
+    // Query strings decode an unescaped `+` into a space.
+    if location.len() > PLUS_CODE_SEPARATOR_POSITION && location.as_bytes()[PLUS_CODE_SEPARATOR_POSITION] == b' '
+        && location[..PLUS_CODE_SEPARATOR_POSITION].chars().all(|character| character == '0' || PLUS_CODE_ALPHABET.contains(character.to_ascii_uppercase())) {
+        return decode_plus_code(&location.replacen(' ', "+", 1));
+    }
+    let mgrs: String = location.split_whitespace().collect::<String>().to_uppercase();
+    if let Some(reference) = parse_mgrs(&mgrs) {
+        return decode_mgrs(reference);
+    }
+    decode_geohash(location)
+}
+
+/// Decodes a geohash into the centre of its cell.
+///
+/// # Arguments
+/// * `geohash` - The geohash, case insensitive.
+///
+/// # Returns
+/// * `Ok((f64, f64))` with latitude and longitude.
+/// * `Err(String)` if the geohash contains invalid characters.
+pub fn decode_geohash(geohash: &str) -> Result<(f64, f64), String> {
+    if geohash.is_empty() {
+        return Err("Empty location.".to_string());
+    }
+    let mut lat_range = (-90f64, 90f64);
+    let mut lon_range = (-180f64, 180f64);
+    let mut is_lon = true;
+    for character in geohash.to_lowercase().chars() {
+        let value = match GEOHASH_ALPHABET.find(character) {
+            Some(value) => value,
+            None => return Err(format!("Unknown location format {}.", geohash))
+        };
+        for bit in (0..5).rev() {
+            let range = if is_lon { &mut lon_range } else { &mut lat_range };
+            let middle = (range.0 + range.1) / 2f64;
+            if (value >> bit) & 1 == 1 {
+                range.0 = middle;
+            } else {
+                range.1 = middle;
+            }
+            is_lon = !is_lon;
+        }
+    }
+    Ok(((lat_range.0 + lat_range.1) / 2f64, (lon_range.0 + lon_range.1) / 2f64))
+}
+
+/// Decodes a full Open Location Code (plus code) into the centre of its cell.
+///
+/// # Arguments
+/// * `code` - The plus code, case insensitive.
+///
+/// # Returns
+/// * `Ok((f64, f64))` with latitude and longitude.
+/// * `Err(String)` if the code is invalid or a short code without reference location.
+pub fn decode_plus_code(code: &str) -> Result<(f64, f64), String> {
+    let code = code.to_uppercase();
+    match code.find('+') {
+        Some(PLUS_CODE_SEPARATOR_POSITION) => {},
+        Some(_) => return Err(format!("Short plus code {} is not supported, use the full code.", code)),
+        None => return Err(format!("Invalid plus code {}.", code))
+    };
+    let digits: Vec<usize> = match code.chars()
+        .filter(|character| *character != '+' && *character != '0')
+        .map(|character| PLUS_CODE_ALPHABET.find(character))
+        .collect::<Option<Vec<usize>>>() {
+        Some(digits) => digits,
+        None => return Err(format!("Invalid plus code {}.", code))
+    };
+    if digits.len() < 2 || (digits.len() % 2 == 1 && digits.len() < 10) {
+        return Err(format!("Invalid plus code {}.", code));
+    }
+    let mut lat = -90f64;
+    let mut lon = -180f64;
+    let mut lat_resolution = 400f64;
+    let mut lon_resolution = 400f64;
+    for pair in digits.iter().take(10).collect::<Vec<&usize>>().chunks(2) {
+        lat_resolution /= 20f64;
+        lon_resolution /= 20f64;
+        lat += *pair[0] as f64 * lat_resolution;
+        lon += *pair[1] as f64 * lon_resolution;
+    }
+    // Digits after the tenth refine the cell on a 4 x 5 grid.
+    for digit in digits.iter().skip(10) {
+        lat_resolution /= 5f64;
+        lon_resolution /= 4f64;
+        lat += (digit / 4) as f64 * lat_resolution;
+        lon += (digit % 4) as f64 * lon_resolution;
+    }
+    Ok(((lat + lat_resolution / 2f64).min(90f64), lon + lon_resolution / 2f64))
+}
+
+/// Components of an MGRS grid reference.
+struct MgrsReference {
+    zone: u32,
+    band: char,
+    column: char,
+    row: char,
+    easting: f64,
+    northing: f64,
+    /// Size of the referenced cell in metres.
+    precision: f64,
+}
+
+/// Splits an MGRS grid reference like `32UMU1234567890` into its components.
+///
+/// # Arguments
+/// * `mgrs` - Upper case grid reference without whitespace.
+///
+/// # Returns
+/// The components, or `None` if the string is not an MGRS grid reference.
+fn parse_mgrs(mgrs: &str) -> Option<MgrsReference> {
+    let zone_length = mgrs.chars().take_while(|character| character.is_ascii_digit()).count();
+    if !(1..=2).contains(&zone_length) {
+        return None;
+    }
+    let zone = mgrs[..zone_length].parse::<u32>().ok()?;
+    let mut letters = mgrs[zone_length..].chars();
+    let band = letters.next()?;
+    let column = letters.next()?;
+    let row = letters.next()?;
+    let digits: String = letters.collect();
+    if !(1..=60).contains(&zone) || !MGRS_BANDS.contains(band) || !column.is_ascii_uppercase() || !MGRS_ROWS.contains(row)
+        || digits.len() % 2 == 1 || digits.len() > 10 || !digits.chars().all(|character| character.is_ascii_digit()) {
+        return None;
+    }
+    let half = digits.len() / 2;
+    let precision = 10f64.powi(5 - half as i32);
+    let easting = if half > 0 { digits[..half].parse::<f64>().ok()? * precision } else { 0f64 };
+    let northing = if half > 0 { digits[half..].parse::<f64>().ok()? * precision } else { 0f64 };
+    Some(MgrsReference {zone, band, column, row, easting, northing, precision})
+}
+
+/// Decodes an MGRS grid reference into the centre of the referenced cell.
+///
+/// # Arguments
+/// * `reference` - The components of the grid reference.
+///
+/// # Returns
+/// * `Ok((f64, f64))` with latitude and longitude.
+/// * `Err(String)` if the letters are inconsistent with the zone or the UTM conversion fails.
+fn decode_mgrs(reference: MgrsReference) -> Result<(f64, f64), String> {
+    let set = ((reference.zone - 1) % 6) as usize;
+    let column_index = match MGRS_COLUMNS[set % 3].find(reference.column) {
+        Some(column_index) => column_index,
+        None => return Err(format!("Invalid MGRS column letter {} for zone {}.", reference.column, reference.zone))
+    };
+    // Row letters of even numbered zones start five letters later.
+    let row_offset = if set % 2 == 1 { 5 } else { 0 };
+    let row_index = match MGRS_ROWS.find(reference.row) {
+        Some(row_index) => (row_index + MGRS_ROWS.len() - row_offset) % MGRS_ROWS.len(),
+        None => return Err(format!("Invalid MGRS row letter {}.", reference.row))
+    };
+    let band_index = MGRS_BANDS.find(reference.band).unwrap_or(0);
+    let north = reference.band >= 'N';
+    let utm_epsg = if north { 32600 + reference.zone } else { 32700 + reference.zone };
+    let wgs84 = epsg_spatial_ref(4326)?;
+    let utm = epsg_spatial_ref(utm_epsg)?;
+
+    // The row letters repeat every 2000 km, the latitude band selects the repetition.
+    let to_utm = match CoordTransform::new(&wgs84, &utm) {
+        Ok(to_utm) => to_utm,
+        Err(e) => return Err(format!("CoordTransform could not be created: {}", e))
+    };
+    let mut x_coord = [(reference.zone as f64 - 1f64) * 6f64 - 177f64];
+    let mut y_coord = [-80f64 + band_index as f64 * 8f64];
+    if let Err(e) = to_utm.transform_coords(&mut x_coord, &mut y_coord, &mut [0.0]) {
+        return Err(format!("Transform coords could not be converted: {}", e));
+    }
+    let band_min_northing = y_coord[0];
+    let easting = (column_index + 1) as f64 * 100_000f64 + reference.easting + reference.precision / 2f64;
+    let mut northing = row_index as f64 * 100_000f64 + reference.northing + reference.precision / 2f64;
+    while northing < band_min_northing - 100_000f64 {
+        northing += 2_000_000f64;
+    }
+
+    let to_wgs84 = match CoordTransform::new(&utm, &wgs84) {
+        Ok(to_wgs84) => to_wgs84,
+        Err(e) => return Err(format!("CoordTransform could not be created: {}", e))
+    };
+    let mut x_coord = [easting];
+    let mut y_coord = [northing];
+    if let Err(e) = to_wgs84.transform_coords(&mut x_coord, &mut y_coord, &mut [0.0]) {
+        return Err(format!("Transform coords could not be converted: {}", e));
+    }
+    Ok((y_coord[0], x_coord[0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Result<(f64, f64), String>, expected: (f64, f64)) {
+        let (lat, lon) = actual.unwrap();
+        assert!((lat - expected.0).abs() < 1e-5 && (lon - expected.1).abs() < 1e-5, "{} {} != {:?}", lat, lon, expected);
+    }
+
+    #[test]
+    fn decodes_plus_codes() {
+        assert_close(decode_location("8FVC9G8F+6X"), (47.3655625, 8.5249375));
+        assert_close(decode_location("8fvc9g8f+6x"), (47.3655625, 8.5249375));
+        // A `+` in a query string arrives as a space.
+        assert_close(decode_location("8FVC9G8F 6X"), (47.3655625, 8.5249375));
+        assert!(decode_location("9G8F+6X").is_err());
+    }
+
+    #[test]
+    fn decodes_geohashes() {
+        assert_close(decode_location("u33dc0"), (52.517395, 13.408813));
+        assert!(decode_location("u33dca").is_err());
+    }
+
+    #[test]
+    fn decodes_mgrs_references() {
+        assert_close(decode_location("4QFJ1234567890"), (21.409801, -157.916076));
+        assert_close(decode_location("4Q FJ 12345 67890"), (21.409801, -157.916076));
+        assert_close(decode_location("34HBH6188143182"), (-33.924899, 18.424099));
+    }
+}
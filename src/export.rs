@@ -0,0 +1,280 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+    extract::{State},
+};
+use axum_macros::debug_handler;
+use gdal::{Dataset, DriverManager, Metadata};
+use gdal::programs::raster::{build_vrt, BuildVRTOptions};
+use gdal::raster::RasterCreationOptions;
+use gdal::spatial_ref::{CoordTransform, SpatialRef};
+use geo::{coord, BoundingRect, Contains, Geometry, Point, Polygon, Rect};
+use geozero::{geojson::GeoJson, ToGeo};
+use serde::Deserialize;
+use std::path::Path;
+use crate::{AppState, error_response};
+use crate::datum::{GeoidModels, VerticalDatum};
+use crate::raster::{encode_dataset, epsg_spatial_ref, source_datasets, target_datum, SourceDataset};
+
+/// NoData value of exported rasters.
+const NO_DATA: f64 = -32768.0;
+/// Upper bound for the number of pixels of a single export.
+const MAX_PIXELS: usize = 25_000_000;
+
+#[derive(Deserialize)]
+pub struct PostExport {
+    /// Bounding box in EPSG 4326 as `[min_lon, min_lat, max_lon, max_lat]`.
+    bbox: Option<[f64; 4]>,
+    /// GeoJSON polygon geometry in EPSG 4326, pixels outside of it are set to NoData.
+    polygon: Option<serde_json::Value>,
+    /// EPSG code of the output coordinate system.
+    srs: Option<u32>,
+    /// Pixel size in units of the output coordinate system.
+    resolution: f64,
+    /// Output format, one of `geotiff` or `cog`.
+    format: Option<String>,
+    /// Layer that is exported, `$DEFAULTLAYER` if not set.
+    layer: Option<String>,
+    /// Vertical datum of the export, that of the preferred dataset if not set.
+    datum: Option<VerticalDatum>,
+}
+
+/// Opens a dataset for warping, reduced to its elevation band if it has several bands.
+///
+/// # Arguments
+/// * `path` - Full path of the dataset.
+/// * `band_index` - Index of the band holding the elevation, starting at 1.
+///
+/// # Returns
+/// * `Ok(Dataset)` with the elevation as its only band.
+/// * `Err(String)` if the dataset could not be opened.
+fn open_elevation_band(path: &Path, band_index: usize) -> Result<Dataset, String> {
+    let dataset = match Dataset::open(path) {
+        Ok(dataset) => dataset,
+        Err(e) => return Err(format!("Dataset could not be opened: {}", e))
+    };
+    if dataset.raster_count() == 1 && band_index == 1 {
+        return Ok(dataset);
+    }
+    let band_index = band_index.to_string();
+    let options = match BuildVRTOptions::new(["-b", band_index.as_str()]) {
+        Ok(options) => options,
+        Err(e) => return Err(format!("VRT options could not be created: {}", e))
+    };
+    match build_vrt(None::<&Path>, &[dataset], Some(options)) {
+        Ok(vrt) => Ok(vrt),
+        Err(e) => Err(format!("Band {} could not be selected: {}", band_index, e))
+    }
+}
+
+/// Creates an in-memory raster of the output grid filled with NoData.
+///
+/// # Arguments
+/// * `bounds` - Output bounds as `[min_x, min_y, max_x, max_y]`.
+/// * `resolution` - Pixel size in units of the output coordinate system.
+/// * `size` - Number of columns and rows.
+/// * `spatial_ref` - Output coordinate system.
+///
+/// # Returns
+/// * `Ok(Dataset)` with a single `f32` band.
+/// * `Err(String)` if the raster could not be created.
+fn empty_raster(bounds: &[f64; 4], resolution: f64, size: (usize, usize), spatial_ref: &SpatialRef) -> Result<Dataset, String> {
+    let mem_driver = DriverManager::get_driver_by_name("MEM").map_err(|e| e.to_string())?;
+    let mut raster = mem_driver.create_with_band_type::<f32, _>("", size.0, size.1, 1).map_err(|e| e.to_string())?;
+    raster.set_geo_transform(&[bounds[0], resolution, 0f64, bounds[3], 0f64, -resolution]).map_err(|e| e.to_string())?;
+    raster.set_spatial_ref(spatial_ref).map_err(|e| e.to_string())?;
+    let mut rasterband = raster.rasterband(1).map_err(|e| e.to_string())?;
+    rasterband.set_no_data_value(Some(NO_DATA)).map_err(|e| e.to_string())?;
+    rasterband.fill(NO_DATA, None).map_err(|e| e.to_string())?;
+    Ok(raster)
+}
+
+/// Computes the longitude and latitude of the centre of every output pixel, row by row.
+///
+/// # Arguments
+/// * `to_wgs84` - Transformation from the output coordinate system into EPSG 4326.
+/// * `bounds` - Output bounds as `[min_x, min_y, max_x, max_y]`.
+/// * `resolution` - Pixel size in units of the output coordinate system.
+/// * `size` - Number of columns and rows.
+///
+/// # Returns
+/// * `Ok((Vec<f64>, Vec<f64>))` with the longitudes and the latitudes.
+/// * `Err(String)` if the coordinates could not be transformed.
+fn pixel_coordinates(to_wgs84: &CoordTransform, bounds: &[f64; 4], resolution: f64, size: (usize, usize)) -> Result<(Vec<f64>, Vec<f64>), String> {
+    let (width, height) = size;
+    let mut x_coord: Vec<f64> = Vec::with_capacity(width * height);
+    let mut y_coord: Vec<f64> = Vec::with_capacity(width * height);
+    for row in 0..height {
+        for col in 0..width {
+            x_coord.push(bounds[0] + (col as f64 + 0.5) * resolution);
+            y_coord.push(bounds[3] - (row as f64 + 0.5) * resolution);
+        }
+    }
+    let mut z_coord = vec![0f64; width * height];
+    to_wgs84.transform_coords(&mut x_coord, &mut y_coord, &mut z_coord).map_err(|e| e.to_string())?;
+    Ok((x_coord, y_coord))
+}
+
+/// Mosaics all datasets into an output raster and encodes it.
+///
+/// Datasets are warped with bilinear resampling from the least preferred to the preferred one,
+/// so the preferred dataset ends up on top while NoData pixels never overwrite valid ones.
+/// Datasets in another vertical datum are warped on their own and converted pixel by pixel
+/// before they are laid over the output.
+///
+/// # Arguments
+/// * `datasets` - Datasets of the export, preferred dataset first.
+/// * `datum` - Geoid grids and vertical datum of the output, `None` if it is unknown.
+/// * `area` - Polygon in EPSG 4326 covering the export.
+/// * `clip` - Whether pixels outside `area` are set to NoData.
+/// * `srs` - EPSG code of the output coordinate system.
+/// * `resolution` - Pixel size in units of the output coordinate system.
+/// * `driver_name` - Short name of the output driver.
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` with the encoded raster.
+/// * `Err((StatusCode, String))` if the export could not be created.
+fn export_raster(datasets: Vec<SourceDataset>, datum: Option<(&GeoidModels, VerticalDatum)>, area: Polygon<f64>, clip: bool, srs: u32, resolution: f64, driver_name: &str) -> Result<Vec<u8>, (StatusCode, String)> {
+    let internal_error = |e: String| {eprintln!("{}", e); (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string())};
+    let wgs84 = epsg_spatial_ref(4326).map_err(internal_error)?;
+    let target = epsg_spatial_ref(srs).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let bbox = match area.bounding_rect() {
+        Some(bbox) => bbox,
+        None => return Err((StatusCode::BAD_REQUEST, "Area must not be empty.".to_string()))
+    };
+    let to_target = CoordTransform::new(&wgs84, &target).map_err(|e| internal_error(e.to_string()))?;
+    let bounds = match to_target.transform_bounds(&[bbox.min().x, bbox.min().y, bbox.max().x, bbox.max().y], 21) {
+        Ok(bounds) => bounds,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, format!("Area could not be transformed to EPSG:{}: {}", srs, e)))
+    };
+    let width = ((bounds[2] - bounds[0]) / resolution).ceil().max(1f64) as usize;
+    let height = ((bounds[3] - bounds[1]) / resolution).ceil().max(1f64) as usize;
+    if width.checked_mul(height).is_none_or(|pixels| pixels > MAX_PIXELS) {
+        return Err((StatusCode::BAD_REQUEST, "Area is too large for the requested resolution.".to_string()));
+    }
+
+    let conversion = |dataset: &SourceDataset| match (dataset.vertical_datum, datum) {
+        (Some(from), Some((geoids, to))) if from != to => Some((geoids, from, to)),
+        _ => None
+    };
+    let coordinates = if clip || datasets.iter().any(|dataset| conversion(dataset).is_some()) {
+        let to_wgs84 = CoordTransform::new(&target, &wgs84).map_err(|e| internal_error(e.to_string()))?;
+        Some(pixel_coordinates(&to_wgs84, &bounds, resolution, (width, height)).map_err(internal_error)?)
+    } else {
+        None
+    };
+    let mut output = empty_raster(&bounds, resolution, (width, height), &target).map_err(internal_error)?;
+    for dataset in datasets.iter().rev() {
+        if dataset.vertical_datum.is_none() && datum.is_some() {
+            eprintln!("{}: Unknown vertical datum", dataset.path.display());
+            continue;
+        }
+        let source = match open_elevation_band(&dataset.path, dataset.band_index) {
+            Ok(source) => source,
+            Err(e) => {eprintln!("{}: {}", dataset.path.display(), e); continue}
+        };
+        let ((geoids, from, to), (x_coord, y_coord)) = match (conversion(dataset), &coordinates) {
+            (Some(conversion), Some(coordinates)) => (conversion, coordinates),
+            _ => {
+                if let Err(e) = gdal::raster::reproject(&source, &output) {
+                    eprintln!("Dataset {} could not be warped: {}", dataset.path.display(), e);
+                }
+                continue;
+            }
+        };
+        let converted = empty_raster(&bounds, resolution, (width, height), &target).map_err(internal_error)?;
+        if let Err(e) = gdal::raster::reproject(&source, &converted) {
+            eprintln!("Dataset {} could not be warped: {}", dataset.path.display(), e);
+            continue;
+        }
+        let values = converted.rasterband(1).and_then(|rasterband| rasterband.read_as::<f32>((0, 0), (width, height), (width, height), None))
+            .map_err(|e| internal_error(e.to_string()))?;
+        let mut rasterband = output.rasterband(1).map_err(|e| internal_error(e.to_string()))?;
+        let mut buffer = rasterband.read_as::<f32>((0, 0), (width, height), (width, height), None).map_err(|e| internal_error(e.to_string()))?;
+        for (index, value) in values.data().iter().enumerate() {
+            if *value == NO_DATA as f32 {
+                continue;
+            }
+            // Pixels the geoid grid does not cover keep the value of the less preferred datasets.
+            if let Ok(value) = geoids.convert(*value as f64, y_coord[index], x_coord[index], from, to) {
+                buffer.data_mut()[index] = value as f32;
+            }
+        }
+        rasterband.write((0, 0), (width, height), &mut buffer).map_err(|e| internal_error(e.to_string()))?;
+    }
+
+    if let (true, Some((x_coord, y_coord))) = (clip, &coordinates) {
+        let mut rasterband = output.rasterband(1).map_err(|e| internal_error(e.to_string()))?;
+        let mut buffer = rasterband.read_as::<f32>((0, 0), (width, height), (width, height), None).map_err(|e| internal_error(e.to_string()))?;
+        for (index, value) in buffer.data_mut().iter_mut().enumerate() {
+            if !area.contains(&Point::new(x_coord[index], y_coord[index])) {
+                *value = NO_DATA as f32;
+            }
+        }
+        rasterband.write((0, 0), (width, height), &mut buffer).map_err(|e| internal_error(e.to_string()))?;
+    }
+    if let Some((_, vertical_datum)) = datum {
+        output.set_metadata_item("VERTICAL_DATUM", vertical_datum.as_str(), "").map_err(|e| internal_error(e.to_string()))?;
+    }
+
+    let mut options = RasterCreationOptions::new();
+    options.set_name_value("COMPRESS", "DEFLATE").map_err(|e| internal_error(e.to_string()))?;
+    if driver_name == "GTiff" {
+        options.set_name_value("TILED", "YES").map_err(|e| internal_error(e.to_string()))?;
+    }
+    encode_dataset(&output, driver_name, &options).map_err(internal_error)
+}
+
+/// Handles POST requests to export a clip of the elevation data as raster.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection.
+/// * `payload` - JSON payload containing the area and the output options.
+///
+/// # Returns
+/// The mosaicked raster as GeoTIFF or Cloud Optimized GeoTIFF.
+#[debug_handler]
+pub async fn post_export(
+    State(appstate): State<AppState>, Json(payload): Json<PostExport>) -> Response {
+    let (area, clip) = match (payload.bbox, &payload.polygon) {
+        (_, Some(polygon)) => match GeoJson(&polygon.to_string()).to_geo() {
+            Ok(Geometry::Polygon(polygon)) => (polygon, true),
+            Ok(_) => return error_response(StatusCode::BAD_REQUEST, "polygon must be a GeoJSON Polygon.".to_string()),
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, format!("polygon could not be parsed: {}", e))
+        },
+        (Some(bbox), None) => (Rect::new(coord! {x: bbox[0], y: bbox[1]}, coord! {x: bbox[2], y: bbox[3]}).to_polygon(), false),
+        (None, None) => return error_response(StatusCode::BAD_REQUEST, "bbox or polygon is a required parameter".to_string())
+    };
+    if payload.resolution <= 0f64 {
+        return error_response(StatusCode::BAD_REQUEST, "resolution must be positive.".to_string());
+    }
+    let driver_name = match payload.format.as_deref().unwrap_or("geotiff") {
+        "geotiff" => "GTiff",
+        "cog" => "COG",
+        format => return error_response(StatusCode::BAD_REQUEST, format!("Unknown format {}.", format))
+    };
+    let layer = payload.layer.unwrap_or_else(|| appstate.ingest_config.default_layer.clone());
+    let datasets = match source_datasets(&appstate, &Geometry::from(area.clone()), &layer).await {
+        Ok(datasets) => datasets,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e)
+    };
+    if datasets.is_empty() {
+        return error_response(StatusCode::NOT_FOUND, "No elevation data inside area.".to_string());
+    }
+    let datum = match target_datum(&appstate.geoids, &datasets, payload.datum) {
+        Ok(datum) => datum,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e)
+    };
+    let srs = payload.srs.unwrap_or(4326);
+    let resolution = payload.resolution;
+    let geoids = appstate.geoids.clone();
+    let exported = tokio::task::spawn_blocking(move || {
+        export_raster(datasets, datum.map(|datum| (&geoids, datum)), area, clip, srs, resolution, driver_name)
+    }).await;
+    match exported {
+        Ok(Ok(bytes)) => (StatusCode::OK, [(header::CONTENT_TYPE, "image/tiff"), (header::CONTENT_DISPOSITION, "attachment; filename=\"export.tif\"")], bytes).into_response(),
+        Ok(Err((status, e))) => error_response(status, e),
+        Err(e) => {eprintln!("{:?}", e); error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string())}
+    }
+}
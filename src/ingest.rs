@@ -0,0 +1,1374 @@
+use gdal::{Dataset, GeoTransform, Metadata};
+use gdal::spatial_ref::{AxisMappingStrategy, CoordTransform, SpatialRef};
+use geo::{coord, BooleanOps, BoundingRect, Geometry, MultiPolygon, Polygon, Rect};
+use gdal::programs::raster::BuildVRTOptions;
+use geozero::wkb;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::UNIX_EPOCH;
+use tokio_postgres::Transaction;
+use tokio_postgres::types::ToSql;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+use crate::ConnectionPool;
+use crate::datum::{detect_vertical_datum, VerticalDatum};
+use crate::raster::{dataset_path, epsg_spatial_ref, remote_dataset_path, DATASET_ORDER, METRES_PER_DEGREE};
+
+/// Number of rows inserted per INSERT statement.
+const INSERT_BATCH_SIZE: usize = 500;
+/// Number of parameters of a single inserted row.
+const INSERT_COLUMNS: usize = 17;
+/// Size in pixels below which no further overview level is built.
+const MIN_OVERVIEW_SIZE: usize = 256;
+/// Number of inspected files between two progress messages.
+const PROGRESS_INTERVAL: usize = 1000;
+/// File extensions of raster formats that are indexed, compared in lower case.
+const RASTER_EXTENSIONS: [&str; 14] = ["tif", "tiff", "hgt", "img", "asc", "dem", "bil", "bt", "flt", "jp2", "nc", "dt0", "dt1", "dt2"];
+/// Extension appended to the name of a file to get the name of its metadata sidecar.
+const SIDECAR_EXTENSION: &str = ".json";
+/// Key of the advisory lock serialising the transactions that replace geo_data rows.
+///
+/// Without it two syncs of the same file both delete the old rows before either inserts, and
+/// both insert their rows. One lock for all paths also covers directories and the files below them.
+const GEO_DATA_LOCK: i64 = 0x6765_6f5f_6461_7461;
+/// Version of the rows written by an ingestion, raised when existing rows need to be reinspected.
+const INDEX_VERSION: u32 = 2;
+
+/// How VRT files in the data directory are indexed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum VrtMode {
+    /// Index the VRT as one dataset whose footprint is the union of its sources.
+    Dataset,
+    /// Index every source of the VRT as a dataset of its own.
+    Expand,
+}
+
+/// Configuration of the dataset ingestion.
+#[derive(Clone)]
+pub struct IngestConfig {
+    /// Vertical datum of datasets whose coordinate system does not state one, read from `$DEFAULTDATUM`.
+    default_datum: VerticalDatum,
+    /// Layer of datasets placed directly in the data directory, read from `$DEFAULTLAYER`.
+    /// Grid requests without a layer read this layer.
+    pub default_layer: String,
+    /// Layer names of top level directories, read from `$LAYERMAP` as `directory=layer,...`.
+    /// Directories that are not listed form a layer of their own name.
+    layer_map: HashMap<String, String>,
+    /// Priorities of top level directories, read from `$PRIORITIES` as `directory=priority,...`.
+    /// Datasets of a higher priority are preferred regardless of their resolution, unlisted directories have priority 0.
+    priorities: HashMap<String, i32>,
+    /// Reinspect every file instead of only new and changed ones.
+    pub full: bool,
+    /// Number of threads inspecting datasets.
+    pub workers: usize,
+    /// Abort the run without changing geo_data if any file fails.
+    pub strict: bool,
+    /// Build overviews for local rasters that have none.
+    pub build_overviews: bool,
+    /// How VRT files are indexed, read from `$VRTMODE` as `dataset` or `expand`.
+    vrt_mode: VrtMode,
+    /// Directory a VRT per layer is written to after indexing, read from `$LAYERVRTDIR`.
+    /// Grid requests and exports read a layer from its VRT instead of its individual datasets.
+    layer_vrt_dir: Option<PathBuf>,
+}
+
+/// Outcome of a single file in an ingestion run.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    /// New or changed file written to geo_data.
+    Indexed,
+    /// Unchanged file that was not inspected again.
+    Skipped,
+    /// File that could not be read.
+    Failed,
+    /// Row removed because its file vanished.
+    Removed,
+}
+
+/// Report entry of a single file.
+#[derive(Serialize)]
+pub struct FileReport {
+    /// Path relative to the data directory.
+    path: String,
+    status: FileStatus,
+    /// Reason of the status, set for failed files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// Report of an ingestion run with the outcome of every file.
+#[derive(Default, Serialize)]
+pub struct IngestReport {
+    pub indexed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub removed: usize,
+    /// Whether the changes were written to geo_data, `false` if a strict run was aborted.
+    pub committed: bool,
+    files: Vec<FileReport>,
+}
+
+impl IngestReport {
+    /// Records the outcome of a file and updates the counts.
+    fn add(&mut self, path: String, status: FileStatus, reason: Option<String>) {
+        match status {
+            FileStatus::Indexed => self.indexed += 1,
+            FileStatus::Skipped => self.skipped += 1,
+            FileStatus::Failed => {
+                println!("{}: {}", path, reason.as_deref().unwrap_or("failed"));
+                self.failed += 1
+            },
+            FileStatus::Removed => self.removed += 1,
+        };
+        self.files.push(FileReport {path, status, reason});
+    }
+}
+
+/// Parses a mapping of the form `key=value,...` from an environment variable.
+///
+/// # Arguments
+/// * `name` - Name of the environment variable.
+///
+/// # Returns
+/// * `Ok(HashMap<String, String>)` with the entries, empty if the variable is not set.
+/// * `Err(String)` if an entry has no `=`.
+fn env_mapping(name: &str) -> Result<HashMap<String, String>, String> {
+    let mut mapping = HashMap::new();
+    if let Ok(mapping_string) = env::var(name) {
+        for entry in mapping_string.split(",").filter(|entry| !entry.is_empty()) {
+            match entry.split_once("=") {
+                Some((key, value)) => mapping.insert(key.trim().to_string(), value.trim().to_string()),
+                None => return Err(format!("Invalid {} entry {}", name, entry))
+            };
+        }
+    }
+    Ok(mapping)
+}
+
+impl IngestConfig {
+    /// Reads the ingestion configuration from the environment.
+    pub fn from_env() -> Result<IngestConfig, String> {
+        let default_datum = match env::var("DEFAULTDATUM") {
+            Ok(default_datum) => match VerticalDatum::from_name(&default_datum) {
+                Some(default_datum) => default_datum,
+                None => return Err("Invalid value for DEFAULTDATUM".to_string())
+            },
+            Err(_) => VerticalDatum::Egm96
+        };
+        let default_layer = env::var("DEFAULTLAYER").unwrap_or_else(|_| "default".to_string());
+        let layer_map = env_mapping("LAYERMAP")?;
+        let mut priorities = HashMap::new();
+        for (directory, priority) in env_mapping("PRIORITIES")? {
+            match priority.parse::<i32>() {
+                Ok(priority) => priorities.insert(directory, priority),
+                Err(_) => return Err(format!("Invalid priority {} for {} in PRIORITIES", priority, directory))
+            };
+        }
+        let workers = thread::available_parallelism().map_or(1, |workers| workers.get());
+        let vrt_mode = match env::var("VRTMODE").as_deref() {
+            Ok("dataset") | Err(_) => VrtMode::Dataset,
+            Ok("expand") => VrtMode::Expand,
+            Ok(_) => return Err("Invalid value for VRTMODE".to_string())
+        };
+        let layer_vrt_dir = env::var("LAYERVRTDIR").ok().map(PathBuf::from);
+        Ok(IngestConfig {default_datum, default_layer, layer_map, priorities, full: false, workers, strict: false, build_overviews: false, vrt_mode, layer_vrt_dir})
+    }
+
+    /// Returns a description of every setting that changes the rows written for a dataset.
+    ///
+    /// An index stored with another fingerprint is reinspected completely.
+    fn fingerprint(&self) -> String {
+        let mut layer_map: Vec<String> = self.layer_map.iter().map(|(directory, layer)| format!("{}={}", directory, layer)).collect();
+        layer_map.sort();
+        let mut priorities: Vec<String> = self.priorities.iter().map(|(directory, priority)| format!("{}={}", directory, priority)).collect();
+        priorities.sort();
+        let vrt_mode = match self.vrt_mode {
+            VrtMode::Dataset => "dataset",
+            VrtMode::Expand => "expand"
+        };
+        format!("version={};datum={};layer={};layermap={};priorities={};vrtmode={}",
+                INDEX_VERSION, self.default_datum.as_str(), self.default_layer, layer_map.join(","), priorities.join(","), vrt_mode)
+    }
+
+    /// Returns the top level directory of a dataset, `None` for datasets directly in the data directory.
+    ///
+    /// # Arguments
+    /// * `filepath` - Path of the dataset relative to the data directory.
+    fn source(&self, filepath: &Path) -> Option<String> {
+        let mut components = filepath.components();
+        match (components.next(), components.next()) {
+            (Some(directory), Some(_)) => Some(directory.as_os_str().to_string_lossy().to_string()),
+            _ => None
+        }
+    }
+
+    /// Returns the layer of a dataset from its top level directory.
+    ///
+    /// # Arguments
+    /// * `filepath` - Path of the dataset relative to the data directory.
+    fn layer(&self, filepath: &Path) -> String {
+        match self.source(filepath) {
+            Some(directory) => self.layer_map.get(&directory).cloned().unwrap_or(directory),
+            None => self.default_layer.clone()
+        }
+    }
+
+    /// Returns the priority of a dataset from its top level directory.
+    ///
+    /// # Arguments
+    /// * `filepath` - Path of the dataset relative to the data directory.
+    fn priority(&self, filepath: &Path) -> i32 {
+        self.source(filepath).and_then(|directory| self.priorities.get(&directory).copied()).unwrap_or(0)
+    }
+
+    /// Returns whether a path lies in the directory of the layer VRTs, which is never indexed.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file including the data directory.
+    fn in_layer_vrt_dir(&self, path: &Path) -> bool {
+        self.layer_vrt_dir.as_ref().is_some_and(|layer_vrt_dir| path.starts_with(layer_vrt_dir))
+    }
+
+    /// Returns the VRT written for a layer after indexing.
+    ///
+    /// # Arguments
+    /// * `layer` - Name of the layer.
+    ///
+    /// # Returns
+    /// The path of the VRT, or `None` if layer VRTs are disabled or the layer has none.
+    pub fn layer_vrt(&self, layer: &str) -> Option<PathBuf> {
+        if layer.is_empty() || layer.starts_with('.') || layer.contains(['/', '\\']) {
+            return None;
+        }
+        self.layer_vrt_dir.as_ref().map(|layer_vrt_dir| layer_vrt_dir.join(format!("{}.vrt", layer))).filter(|vrt_path| vrt_path.is_file())
+    }
+}
+
+/// Computes the ground resolution of a dataset, the larger of its pixel width and height in metres.
+///
+/// # Arguments
+/// * `spatial_ref` - Coordinate system of the dataset.
+/// * `geotransform` - Geotransform of the dataset.
+/// * `center_lat` - Latitude of the centre of the dataset, used to scale longitudes.
+///
+/// # Returns
+/// The ground resolution in metres.
+pub fn ground_resolution(spatial_ref: &SpatialRef, geotransform: &GeoTransform, center_lat: f64) -> f64 {
+    let pixel_width = geotransform[1].hypot(geotransform[4]);
+    let pixel_height = geotransform[2].hypot(geotransform[5]);
+    if spatial_ref.is_projected() {
+        pixel_width.max(pixel_height) * spatial_ref.linear_units()
+    } else {
+        (pixel_width * center_lat.to_radians().cos()).max(pixel_height) * METRES_PER_DEGREE
+    }
+}
+
+/// Reads the acquisition date of a dataset from its metadata.
+///
+/// # Arguments
+/// * `dataset` - The dataset.
+///
+/// # Returns
+/// The date as `YYYY-MM-DD`, or `None` if the metadata does not contain one.
+fn acquisition_date(dataset: &Dataset) -> Option<String> {
+    ["ACQUISITION_DATE", "ACQUISITIONDATE", "TIFFTAG_DATETIME"].iter()
+        .filter_map(|key| dataset.metadata_item(key, ""))
+        .filter_map(|value| value.get(..10).map(|date| date.replace(":", "-")))
+        .find(|date| date.chars().enumerate().all(|(index, character)| if index == 4 || index == 7 { character == '-' } else { character.is_ascii_digit() }))
+}
+
+/// Reads the stated vertical accuracy of a dataset in metres from its metadata.
+///
+/// # Arguments
+/// * `dataset` - The dataset.
+///
+/// # Returns
+/// The vertical accuracy, or `None` if the metadata does not contain one.
+fn vertical_accuracy(dataset: &Dataset) -> Option<f64> {
+    ["VERTICAL_ACCURACY", "VERTICAL_ACCURACY_M", "ACCURACY_VERTICAL", "RMSE_Z", "LE90"].iter()
+        .filter_map(|key| dataset.metadata_item(key, ""))
+        .find_map(|value| value.trim().trim_end_matches('m').trim().parse::<f64>().ok())
+}
+
+/// Reads the name of a dataset from its metadata.
+///
+/// # Arguments
+/// * `dataset` - The dataset.
+///
+/// # Returns
+/// The name, or `None` if the metadata does not contain one.
+fn dataset_name(dataset: &Dataset) -> Option<String> {
+    ["DATASET_NAME", "TITLE", "TIFFTAG_DOCUMENTNAME", "PRODUCT_NAME"].iter()
+        .filter_map(|key| dataset.metadata_item(key, ""))
+        .map(|value| value.trim().to_string())
+        .find(|value| !value.is_empty())
+}
+
+/// Returns the names of the horizontal and vertical coordinate system of a dataset.
+///
+/// # Arguments
+/// * `spatial_ref` - Coordinate system of the dataset.
+///
+/// # Returns
+/// The horizontal coordinate system as `AUTHORITY:CODE` if known or by name, and the name of the
+/// vertical coordinate system if the coordinate system is compound.
+fn crs_names(spatial_ref: &SpatialRef) -> (Option<String>, Option<String>) {
+    let horizontal = match (spatial_ref.auth_name(), spatial_ref.auth_code()) {
+        (Ok(authority), Ok(code)) if !spatial_ref.is_compound() => Some(format!("{}:{}", authority, code)),
+        _ => ["PROJCS", "GEOGCS"].iter().find_map(|node| spatial_ref.get_attr_value(node, 0).ok().flatten())
+    };
+    let vertical = spatial_ref.get_attr_value("VERT_CS", 0).ok().flatten();
+    (horizontal, vertical)
+}
+
+/// Metadata of a file given in its sidecar, overriding the metadata read from the datasets.
+///
+/// The sidecar of `dem.tif` is `dem.tif.json` and applies to every dataset of the file, e.g.
+/// all members of an archive.
+#[derive(Default, Deserialize)]
+struct Sidecar {
+    name: Option<String>,
+    /// Acquisition date as `YYYY-MM-DD`.
+    acquisition_date: Option<String>,
+    horizontal_crs: Option<String>,
+    vertical_crs: Option<String>,
+    /// Vertical accuracy in metres.
+    vertical_accuracy: Option<f64>,
+}
+
+/// Returns the path of the sidecar of a file.
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_os_string();
+    sidecar.push(SIDECAR_EXTENSION);
+    PathBuf::from(sidecar)
+}
+
+/// Returns the file a sidecar describes, if the path is the sidecar of an indexed file.
+///
+/// # Arguments
+/// * `path` - Path of a file relative to the data directory.
+///
+/// # Returns
+/// The path of the described file relative to the data directory, or `None` if the path is no sidecar.
+pub fn sidecar_owner(path: &Path) -> Option<PathBuf> {
+    let name = path.to_string_lossy();
+    let owner = PathBuf::from(name.strip_suffix(SIDECAR_EXTENSION)?);
+    if file_kind(&owner) == FileKind::Other {
+        return None;
+    }
+    Some(owner)
+}
+
+/// Reads the sidecar of a file.
+///
+/// # Arguments
+/// * `path` - Full path of the file.
+///
+/// # Returns
+/// * `Ok(Sidecar)` with the metadata of the sidecar, empty if the file has none.
+/// * `Err(String)` if the sidecar exists but is not valid.
+fn read_sidecar(path: &Path) -> Result<Sidecar, String> {
+    let sidecar = sidecar_path(path);
+    let content = match std::fs::read_to_string(&sidecar) {
+        Ok(content) => content,
+        Err(_) => return Ok(Sidecar::default())
+    };
+    match serde_json::from_str(&content) {
+        Ok(metadata) => Ok(metadata),
+        Err(e) => Err(format!("Sidecar {} is not valid: {}", sidecar.display(), e))
+    }
+}
+
+/// Size and modification time of a dataset file, used to detect changed files.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FileState {
+    /// File size in bytes.
+    size: i64,
+    /// Modification time in seconds since the Unix epoch.
+    modified: i64,
+}
+
+/// Kind of a file in the data directory.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum FileKind {
+    /// Raster dataset GDAL opens directly.
+    Raster,
+    /// Zip or tar archive whose raster members are indexed, with its GDAL virtual file system prefix.
+    Archive(&'static str),
+    /// Gzip compressed raster dataset.
+    Compressed,
+    /// GDAL virtual raster referencing other datasets.
+    Vrt,
+    /// Text file listing URIs of remote datasets, one per line.
+    RemoteList,
+    /// Any other file, skipped.
+    Other,
+}
+
+/// Returns whether a path has one of the raster extensions.
+pub fn has_raster_extension(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| RASTER_EXTENSIONS.contains(&extension.to_string_lossy().to_lowercase().as_str()))
+}
+
+/// Returns whether indexing reads a file: a raster, compressed raster, archive, VRT or remote dataset list.
+pub fn is_indexable(path: &Path) -> bool {
+    file_kind(path) != FileKind::Other
+}
+
+/// Determines the kind of a file from its name.
+fn file_kind(path: &Path) -> FileKind {
+    let name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().to_lowercase());
+    if name.ends_with(".zip") {
+        FileKind::Archive("/vsizip/")
+    } else if name.ends_with(".tar") || name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        FileKind::Archive("/vsitar/")
+    } else if name.ends_with(".gz") && has_raster_extension(Path::new(&name[..name.len() - 3])) {
+        FileKind::Compressed
+    } else if name.ends_with(".vrt") {
+        FileKind::Vrt
+    } else if name.ends_with(".urls") {
+        FileKind::RemoteList
+    } else if has_raster_extension(path) {
+        FileKind::Raster
+    } else {
+        FileKind::Other
+    }
+}
+
+/// Row of geo_data describing a single dataset.
+struct DatasetRecord {
+    /// Dataset path relative to the data directory, behind a GDAL archive prefix for archive members.
+    path: String,
+    /// Path of the file containing the dataset relative to the data directory.
+    file_path: String,
+    file_state: FileState,
+    resolution: i32,
+    vertical_datum: VerticalDatum,
+    layer: String,
+    priority: i32,
+    ground_resolution: f64,
+    acquisition_date: Option<String>,
+    dataset_name: Option<String>,
+    horizontal_crs: Option<String>,
+    vertical_crs: Option<String>,
+    /// Stated vertical accuracy in metres.
+    vertical_accuracy: Option<f64>,
+    /// Coordinate system of the dataset as WKT.
+    projection: String,
+    band_count: i32,
+    /// Footprint in EPSG 4326.
+    footprint: Geometry<f64>,
+}
+
+/// Opens a dataset and collects everything stored about it in geo_data.
+///
+/// Metadata given in the sidecar of the file takes precedence over the metadata of the dataset.
+///
+/// # Arguments
+/// * `datadir` - Directory containing geospatial data.
+/// * `filepath` - Path of the file containing the dataset relative to `datadir`.
+/// * `path` - Dataset path as stored in geo_data.
+/// * `file_state` - Size and modification time of the file.
+/// * `ingest_config` - Configuration of the dataset ingestion.
+///
+/// # Returns
+/// * `Ok(DatasetRecord)` with the row describing the dataset.
+/// * `Err(String)` if the dataset could not be read or its footprint not be computed.
+fn inspect_dataset(datadir: &Path, filepath: &Path, path: String, file_state: FileState, ingest_config: &IngestConfig) -> Result<DatasetRecord, String> {
+    let dataset = match Dataset::open(dataset_path(datadir, &path)){
+        Ok(dataset) => dataset,
+        Err(e) => return Err(format!("Dataset could not be opened: {}", e))
+    };
+    let projection_string = dataset.projection();
+    let mut spat = match SpatialRef::from_esri(&projection_string) {
+        Ok(spatial_ref) => spatial_ref,
+        Err(e) => return Err(format!("Source SpatialRef could not be parsed: {}", e))
+    };
+    // Both coordinate systems take eastings (longitudes) as first axis.
+    spat.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+    let spat_target = epsg_spatial_ref(4326)?;
+    let geo = match CoordTransform::new(&spat, &spat_target) {
+        Ok(geo) => geo,
+        Err(e) => return Err(format!("CoordTransform could not be created: {}", e))
+    };
+    let (width, height) = dataset.raster_size();
+    let geotransform = match dataset.geo_transform() {
+        Ok(geotransform) => geotransform,
+        Err(e) => return Err(format!("Geo transform could not be created: {}", e))
+    };
+    let mut x_coord = [geotransform[0], geotransform[0] + width as f64 * geotransform[1] + height as f64 * geotransform[2]];
+    let mut y_coord = [geotransform[3] + width as f64 * geotransform[4] + height as f64 * geotransform[5],  geotransform[3]];
+    match geo.transform_coords(&mut x_coord, &mut y_coord, &mut [0.0, 0.0]) {
+        Ok(_) => {},
+        Err(e) => return Err(format!("Transform coords could not be converted: {}", e))
+    };
+    let resolution = (width as f64 / ((500f64 + x_coord[0]) - (500f64 + x_coord[1])).abs()) as i32;
+    let coord_1 = geo::Coord::from((x_coord[0], y_coord[0]));
+    let coord_2 = geo::Coord::from((x_coord[0], y_coord[1]));
+    let coord_3 = geo::Coord::from((x_coord[1], y_coord[1]));
+    let coord_4 = geo::Coord::from((x_coord[1], y_coord[0]));
+    let sidecar = read_sidecar(&datadir.join(filepath))?;
+    let (horizontal_crs, vertical_crs) = crs_names(&spat);
+    let default_name = Path::new(&path).file_name().map(|name| name.to_string_lossy().to_string());
+    Ok(DatasetRecord {
+        path,
+        file_path: filepath.to_string_lossy().to_string(),
+        file_state,
+        resolution,
+        vertical_datum: detect_vertical_datum(&projection_string).unwrap_or(ingest_config.default_datum),
+        layer: ingest_config.layer(filepath),
+        priority: ingest_config.priority(filepath),
+        ground_resolution: ground_resolution(&spat, &geotransform, (y_coord[0] + y_coord[1]) / 2f64),
+        acquisition_date: sidecar.acquisition_date.or_else(|| acquisition_date(&dataset)),
+        dataset_name: sidecar.name.or_else(|| dataset_name(&dataset)).or(default_name),
+        horizontal_crs: sidecar.horizontal_crs.or(horizontal_crs),
+        vertical_crs: sidecar.vertical_crs.or(vertical_crs),
+        vertical_accuracy: sidecar.vertical_accuracy.or_else(|| vertical_accuracy(&dataset)),
+        band_count: dataset.raster_count() as i32,
+        projection: projection_string,
+        footprint: Polygon::new(geo::LineString(vec![coord_1, coord_2, coord_3, coord_4]), vec![]).into(),
+    })
+}
+
+/// Builds overviews for a raster that has none, halving the size down to `MIN_OVERVIEW_SIZE`.
+///
+/// Overviews are written to an external `.ovr` file next to the raster.
+///
+/// # Arguments
+/// * `path` - Full path of the raster.
+///
+/// # Returns
+/// * `Ok(usize)` with the number of overview levels built, `0` if the raster already has overviews or is small.
+/// * `Err(String)` if the overviews could not be built.
+fn build_missing_overviews(path: &Path) -> Result<usize, String> {
+    let mut dataset = match Dataset::open(path) {
+        Ok(dataset) => dataset,
+        Err(e) => return Err(format!("Dataset could not be opened: {}", e))
+    };
+    let overview_count = match dataset.rasterband(1).and_then(|rasterband| rasterband.overview_count()) {
+        Ok(overview_count) => overview_count,
+        Err(e) => return Err(format!("Rasterband could not be opened: {}", e))
+    };
+    let (width, height) = dataset.raster_size();
+    let mut levels: Vec<i32> = Vec::new();
+    let mut level = 2;
+    while overview_count == 0 && width.max(height) / level as usize >= MIN_OVERVIEW_SIZE {
+        levels.push(level);
+        level *= 2;
+    }
+    if levels.is_empty() {
+        return Ok(0);
+    }
+    match dataset.build_overviews("AVERAGE", &levels, &[]) {
+        Ok(_) => Ok(levels.len()),
+        Err(e) => Err(format!("Overviews could not be built: {}", e))
+    }
+}
+
+/// Reads the source datasets referenced by a VRT.
+///
+/// # Arguments
+/// * `vrt_path` - Full path of the VRT.
+///
+/// # Returns
+/// * `Ok(Vec<PathBuf>)` with the full path of every distinct source.
+/// * `Err(String)` if the VRT could not be read.
+fn vrt_sources(vrt_path: &Path) -> Result<Vec<PathBuf>, String> {
+    let vrt = match std::fs::read_to_string(vrt_path) {
+        Ok(vrt) => vrt,
+        Err(e) => return Err(format!("VRT could not be read: {}", e))
+    };
+    let vrt_directory = vrt_path.parent().unwrap_or(Path::new(""));
+    let mut sources: Vec<PathBuf> = Vec::new();
+    for element in vrt.split("<SourceFilename").skip(1) {
+        let (attributes, rest) = match element.split_once('>') {
+            Some(parts) => parts,
+            None => continue
+        };
+        let filename = match rest.split_once("</SourceFilename>") {
+            Some((filename, _)) => filename.trim(),
+            None => continue
+        };
+        let source = if attributes.contains("relativeToVRT=\"1\"") {
+            vrt_directory.join(filename)
+        } else {
+            PathBuf::from(filename)
+        };
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+    Ok(sources)
+}
+
+/// Inspects a VRT, either as one dataset covering the union of its sources or as its individual sources.
+///
+/// Sources that are files of the data directory are indexed on their own and left out, so their
+/// datasets are not stored twice.
+///
+/// # Arguments
+/// * `datadir` - Directory containing geospatial data.
+/// * `filepath` - Path of the VRT relative to `datadir`.
+/// * `file_state` - Size and modification time of the VRT.
+/// * `ingest_config` - Configuration of the dataset ingestion.
+///
+/// # Returns
+/// * `Ok(Vec<DatasetRecord>)` with the VRT or its sources.
+/// * `Err(String)` if the VRT or one of its sources could not be read.
+fn inspect_vrt(datadir: &Path, filepath: &Path, file_state: FileState, ingest_config: &IngestConfig) -> Result<Vec<DatasetRecord>, String> {
+    let sources = vrt_sources(&datadir.join(filepath))?;
+    let source_count = sources.len();
+    let mut source_records = Vec::with_capacity(sources.len());
+    for source in sources {
+        if source.strip_prefix(datadir).is_ok_and(|relative| file_kind(relative) != FileKind::Other) && !ingest_config.in_layer_vrt_dir(&source) {
+            continue;
+        }
+        // Sources inside the data directory are stored relative to it, others with their full path.
+        let path = source.strip_prefix(datadir).unwrap_or(&source).to_string_lossy().to_string();
+        match inspect_dataset(datadir, filepath, path.clone(), file_state, ingest_config) {
+            Ok(record) => source_records.push(record),
+            Err(e) => return Err(format!("VRT source {}: {}", path, e))
+        };
+    }
+    if ingest_config.vrt_mode == VrtMode::Expand {
+        return Ok(source_records);
+    }
+    // A VRT mosaicking only indexed files covers nothing of its own.
+    if source_count > 0 && source_records.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut record = inspect_dataset(datadir, filepath, filepath.to_string_lossy().to_string(), file_state, ingest_config)?;
+    if !source_records.is_empty() {
+        let footprint = source_records.iter().fold(MultiPolygon::new(Vec::new()), |footprint, source_record| match &source_record.footprint {
+            Geometry::Polygon(polygon) => footprint.union(&MultiPolygon::new(vec![polygon.clone()])),
+            Geometry::MultiPolygon(multi_polygon) => footprint.union(multi_polygon),
+            _ => footprint
+        });
+        record.footprint = footprint.into();
+    }
+    Ok(vec![record])
+}
+
+/// Builds missing overviews of rasters on several worker threads, reporting the rasters that fail.
+///
+/// # Arguments
+/// * `paths` - Full paths of the rasters.
+/// * `workers` - Number of threads building overviews.
+fn build_overviews_in_parallel(paths: &[PathBuf], workers: usize) {
+    let next_path = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            scope.spawn(|| {
+                while let Some(path) = paths.get(next_path.fetch_add(1, Ordering::Relaxed)) {
+                    if let Err(e) = build_missing_overviews(path) {
+                        println!("{}: {}", path.display(), e);
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Reads the remote datasets listed in a file.
+///
+/// Empty lines and lines starting with `#` are ignored.
+///
+/// # Arguments
+/// * `list_path` - Full path of the list.
+///
+/// # Returns
+/// * `Ok(Vec<String>)` with the GDAL path of every listed dataset.
+/// * `Err(String)` if the list could not be read or contains an unsupported URI.
+fn remote_datasets(list_path: &Path) -> Result<Vec<String>, String> {
+    let list = match std::fs::read_to_string(list_path) {
+        Ok(list) => list,
+        Err(e) => return Err(format!("Remote dataset list could not be read: {}", e))
+    };
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|uri| remote_dataset_path(uri).ok_or_else(|| format!("Unsupported remote dataset {}", uri)))
+        .collect()
+}
+
+/// Lists the datasets of a file as they are stored in geo_data: the file itself, the raster members
+/// of an archive, a compressed raster or the datasets of a remote dataset list. A VRT is listed as itself.
+///
+/// # Arguments
+/// * `datadir` - Directory containing geospatial data.
+/// * `filepath` - Path of the file relative to `datadir`.
+///
+/// # Returns
+/// * `Ok(Vec<String>)` with the dataset paths, resolved with `dataset_path`, empty for files that are not indexed.
+/// * `Err(String)` if the archive or list could not be read.
+pub fn file_datasets(datadir: &Path, filepath: &Path) -> Result<Vec<String>, String> {
+    let relative_path = filepath.to_string_lossy().to_string();
+    match file_kind(filepath) {
+        FileKind::Raster | FileKind::Vrt => Ok(vec![relative_path]),
+        FileKind::Compressed => Ok(vec![format!("/vsigzip/{}", relative_path)]),
+        FileKind::RemoteList => remote_datasets(&datadir.join(filepath)),
+        FileKind::Archive(prefix) => {
+            let members = match gdal::vsi::read_dir(format!("{}{}", prefix, datadir.join(filepath).display()), true) {
+                Ok(members) => members,
+                Err(e) => return Err(format!("Archive could not be read: {}", e))
+            };
+            Ok(members.iter()
+                .filter(|member| has_raster_extension(member))
+                .map(|member| format!("{}{}/{}", prefix, relative_path, member.display()))
+                .collect())
+        },
+        FileKind::Other => Ok(Vec::new())
+    }
+}
+
+/// Inspects every dataset of a file: the file itself, the raster members of an archive, a compressed raster,
+/// the sources of a VRT or the datasets of a remote dataset list.
+///
+/// # Arguments
+/// * `datadir` - Directory containing geospatial data.
+/// * `filepath` - Path of the file relative to `datadir`.
+/// * `file_state` - Size and modification time of the file.
+/// * `ingest_config` - Configuration of the dataset ingestion.
+///
+/// # Returns
+/// * `Ok(Vec<DatasetRecord>)` with one row per dataset, empty for archives or VRTs without raster datasets.
+/// * `Err(String)` if a dataset could not be read.
+fn inspect_file(datadir: &Path, filepath: &Path, file_state: FileState, ingest_config: &IngestConfig) -> Result<Vec<DatasetRecord>, String> {
+    let relative_path = filepath.to_string_lossy().to_string();
+    match file_kind(filepath) {
+        FileKind::Raster if ingest_config.build_overviews => {
+            if let Err(e) = build_missing_overviews(&datadir.join(filepath)) {
+                println!("{}: {}", relative_path, e);
+            }
+        },
+        FileKind::Vrt => return inspect_vrt(datadir, filepath, file_state, ingest_config),
+        _ => {}
+    };
+    let paths = file_datasets(datadir, filepath)?;
+    let mut records = Vec::with_capacity(paths.len());
+    for path in paths {
+        match inspect_dataset(datadir, filepath, path.clone(), file_state, ingest_config) {
+            Ok(record) => records.push(record),
+            Err(e) if path == relative_path => return Err(e),
+            Err(e) => return Err(format!("{}: {}", path, e))
+        };
+    }
+    Ok(records)
+}
+
+/// Returns the size and modification time of a file.
+///
+/// The modification time of the sidecar counts as a modification of the file, so changed
+/// sidecars are picked up by incremental syncs.
+fn file_state(path: &Path) -> Result<FileState, String> {
+    let metadata = match path.metadata() {
+        Ok(metadata) => metadata,
+        Err(e) => return Err(format!("File metadata could not be read: {}", e))
+    };
+    let modified_secs = |metadata: &std::fs::Metadata| match metadata.modified().map(|modified| modified.duration_since(UNIX_EPOCH)) {
+        Ok(Ok(modified)) => modified.as_secs() as i64,
+        _ => 0
+    };
+    let sidecar_modified = sidecar_path(path).metadata().map_or(0, |sidecar_metadata| modified_secs(&sidecar_metadata));
+    Ok(FileState {size: metadata.len() as i64, modified: modified_secs(&metadata).max(sidecar_modified)})
+}
+
+/// Returns the message of a panic payload.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "unknown panic".to_string()
+    }
+}
+
+/// Inspects datasets on several worker threads.
+///
+/// # Arguments
+/// * `datadir` - Directory containing geospatial data.
+/// * `candidates` - Paths relative to `datadir` with their file state.
+/// * `ingest_config` - Configuration of the dataset ingestion.
+///
+/// # Returns
+/// The inspection result of every candidate, in no particular order.
+fn inspect_datasets(datadir: &Path, candidates: Vec<(PathBuf, FileState)>, ingest_config: &IngestConfig) -> Vec<(String, Result<Vec<DatasetRecord>, String>)> {
+    let next_candidate = AtomicUsize::new(0);
+    let inspected = AtomicUsize::new(0);
+    let total = candidates.len();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..ingest_config.workers.max(1)).map(|_| scope.spawn(|| {
+            let mut results = Vec::new();
+            loop {
+                let index = next_candidate.fetch_add(1, Ordering::Relaxed);
+                let (filepath, file_state) = match candidates.get(index) {
+                    Some(candidate) => candidate,
+                    None => break
+                };
+                let path = filepath.to_string_lossy().to_string();
+                // A panic while reading a file fails that file only, the worker keeps its results.
+                let result = match panic::catch_unwind(AssertUnwindSafe(|| inspect_file(datadir, filepath, *file_state, ingest_config))) {
+                    Ok(result) => result,
+                    Err(payload) => Err(format!("Inspection panicked: {}", panic_message(payload.as_ref())))
+                };
+                results.push((path, result));
+                let done = inspected.fetch_add(1, Ordering::Relaxed) + 1;
+                if done.is_multiple_of(PROGRESS_INTERVAL) || done == total {
+                    println!("Inspected {}/{} files", done, total);
+                }
+            }
+            results
+        })).collect();
+        let mut results: Vec<(String, Result<Vec<DatasetRecord>, String>)> = Vec::with_capacity(total);
+        let mut worker_panic: Option<String> = None;
+        for worker in workers {
+            match worker.join() {
+                Ok(worker_results) => results.extend(worker_results),
+                Err(payload) => worker_panic = Some(panic_message(payload.as_ref()))
+            };
+        }
+        // The results of a panicked worker are lost, its candidates are reported as failed.
+        if let Some(message) = worker_panic {
+            let reported: HashSet<String> = results.iter().map(|(path, _)| path.clone()).collect();
+            for (filepath, _) in &candidates {
+                let path = filepath.to_string_lossy().to_string();
+                if !reported.contains(&path) {
+                    results.push((path, Err(format!("Inspection worker panicked: {}", message))));
+                }
+            }
+        }
+        results
+    })
+}
+
+/// Inserts dataset rows with multi-row INSERT statements.
+///
+/// # Arguments
+/// * `transaction` - The transaction the rows are inserted in.
+/// * `records` - The rows to insert.
+///
+/// # Returns
+/// * `Ok(())` if all rows were inserted.
+/// * `Err(tokio_postgres::Error)` if a statement failed.
+async fn insert_records(transaction: &Transaction<'_>, records: &[DatasetRecord]) -> Result<(), tokio_postgres::Error> {
+    for batch in records.chunks(INSERT_BATCH_SIZE) {
+        let vertical_datums: Vec<&str> = batch.iter().map(|record| record.vertical_datum.as_str()).collect();
+        let footprints: Vec<wkb::Encode<Geometry<f64>>> = batch.iter().map(|record| wkb::Encode(record.footprint.clone())).collect();
+        let mut values: Vec<String> = Vec::with_capacity(batch.len());
+        let mut params: Vec<&(dyn ToSql + Sync)> = Vec::with_capacity(batch.len() * INSERT_COLUMNS);
+        for (index, record) in batch.iter().enumerate() {
+            let placeholders: Vec<String> = (1..INSERT_COLUMNS).map(|column| format!("${}", index * INSERT_COLUMNS + column)).collect();
+            values.push(format!("({}, ST_SetSRID(CAST (${} AS geometry),4326))", placeholders.join(", "), (index + 1) * INSERT_COLUMNS));
+            params.extend_from_slice(&[&record.path, &record.file_path, &record.resolution, &vertical_datums[index], &record.layer, &record.priority, &record.ground_resolution,
+                                       &record.acquisition_date, &record.dataset_name, &record.horizontal_crs, &record.vertical_crs, &record.vertical_accuracy,
+                                       &record.projection, &record.band_count, &record.file_state.size, &record.file_state.modified, &footprints[index]]);
+        }
+        transaction.execute(&format!("INSERT INTO geo_data (path,file_path,resolution,vertical_datum,layer,priority,ground_resolution,acquisition_date,dataset_name,horizontal_crs,vertical_crs,vertical_accuracy,projection,band_count,file_size,modified,object) VALUES {}", values.join(", ")), &params).await?;
+    }
+    Ok(())
+}
+
+/// Synchronises geo_data with the datasets in the data directory.
+///
+/// New and changed files (by size and modification time) are inspected in parallel, then vanished
+/// and changed rows are removed and the new rows inserted inside one transaction, so a running
+/// server keeps answering from the previous index until the sync is committed.
+///
+/// # Arguments
+/// * `datadir_path_string` - The path to the directory containing the geospatial data.
+/// * `pool` - The connection pool to the PostgreSQL database.
+/// * `ingest_config` - Configuration of the dataset ingestion.
+///
+/// # Returns
+/// * `Ok(IngestReport)` with the outcome of every file, uncommitted if a strict run had failures.
+/// * `Err(&str)` if the database could not be created or updated.
+pub async fn parse_data_create_database(datadir_path_string: String, pool: ConnectionPool, ingest_config: &IngestConfig) -> Result<IngestReport, &'static str>{
+    let datadir = Path::new(datadir_path_string.as_str());
+    let mut conn = match pool.get().await {
+        Ok(conn) => conn,
+        Err(_) => {return Err("Database Connection could not be established")}
+    };
+
+    match conn.batch_execute("
+        CREATE TABLE IF NOT EXISTS geo_data (
+            id              SERIAL PRIMARY KEY,
+            path            VARCHAR,
+            file_path       VARCHAR,
+            resolution      INTEGER,
+            vertical_datum  VARCHAR,
+            layer           VARCHAR,
+            priority        INTEGER NOT NULL DEFAULT 0,
+            ground_resolution DOUBLE PRECISION,
+            acquisition_date VARCHAR,
+            dataset_name    VARCHAR,
+            horizontal_crs  VARCHAR,
+            vertical_crs    VARCHAR,
+            vertical_accuracy DOUBLE PRECISION,
+            projection      VARCHAR,
+            band_count      INTEGER,
+            file_size       BIGINT,
+            modified        BIGINT,
+            object          GEOMETRY
+            );
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS vertical_datum VARCHAR;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS layer VARCHAR;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS priority INTEGER;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS ground_resolution DOUBLE PRECISION;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS acquisition_date VARCHAR;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS file_size BIGINT;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS modified BIGINT;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS file_path VARCHAR;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS dataset_name VARCHAR;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS horizontal_crs VARCHAR;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS vertical_crs VARCHAR;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS vertical_accuracy DOUBLE PRECISION;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS projection VARCHAR;
+        ALTER TABLE geo_data ADD COLUMN IF NOT EXISTS band_count INTEGER;
+        UPDATE geo_data SET priority = 0 WHERE priority IS NULL;
+        ALTER TABLE geo_data ALTER COLUMN priority SET DEFAULT 0;
+        ALTER TABLE geo_data ALTER COLUMN priority SET NOT NULL;
+        CREATE TABLE IF NOT EXISTS ingest_config (
+            fingerprint     VARCHAR NOT NULL
+            );
+    ").await {
+        Ok(_) => {},
+        Err(_) => {return Err("New Database could not be created")}
+    };
+    // Rows indexed before file paths were stored describe the file at their own path.
+    let rows = match conn.query("SELECT DISTINCT COALESCE(file_path, path) AS file_path, file_size, modified FROM geo_data", &[]).await {
+        Ok(rows) => rows,
+        Err(_) => {return Err("Existing datasets could not be read")}
+    };
+    let mut stored: HashMap<String, Option<FileState>> = HashMap::new();
+    for row in rows {
+        let file_state = match (row.get::<_, Option<i64>>("file_size"), row.get::<_, Option<i64>>("modified")) {
+            (Some(size), Some(modified)) => Some(FileState {size, modified}),
+            _ => None
+        };
+        stored.insert(row.get("file_path"), file_state);
+    }
+    // Rows written with another configuration may have another layer, priority or datum.
+    let fingerprint = ingest_config.fingerprint();
+    let stored_fingerprint: Option<String> = match conn.query_opt("SELECT fingerprint FROM ingest_config LIMIT 1", &[]).await {
+        Ok(row) => row.map(|row| row.get("fingerprint")),
+        Err(_) => {return Err("Ingestion configuration could not be read")}
+    };
+    let full = ingest_config.full || stored_fingerprint.as_ref() != Some(&fingerprint);
+    if full && !ingest_config.full && !stored.is_empty() {
+        println!("Ingestion configuration changed, reinspecting every file....");
+    }
+
+    println!("Walking Directory....");
+    let mut candidates: Vec<(PathBuf, FileState)> = Vec::new();
+    let mut changed: Vec<String> = Vec::new();
+    // Unchanged rasters are not inspected again, so their overviews are built on their own.
+    let mut overview_candidates: Vec<PathBuf> = Vec::new();
+    let mut report = IngestReport::default();
+    for entry in WalkDir::new(datadir) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let path = e.path().and_then(|path| path.strip_prefix(datadir).ok()).map_or(String::new(), |path| path.to_string_lossy().to_string());
+                report.add(path, FileStatus::Failed, Some(format!("Directory entry could not be read: {}", e)));
+                continue
+            }
+        };
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        if ingest_config.in_layer_vrt_dir(entry.path()) {
+            continue;
+        }
+        let filepath = match entry.path().strip_prefix(datadir){
+            Ok(filepath) => filepath,
+            Err(e) => {report.add(entry.path().to_string_lossy().to_string(), FileStatus::Failed, Some(format!("Filepath could not be formated: {}", e))); continue}
+        };
+        let path = filepath.to_string_lossy().to_string();
+        if file_kind(filepath) == FileKind::Other {
+            report.add(path, FileStatus::Skipped, Some("Not a raster file".to_string()));
+            continue;
+        }
+        let file_state = match file_state(entry.path()) {
+            Ok(file_state) => file_state,
+            Err(e) => {report.add(path, FileStatus::Failed, Some(e)); continue}
+        };
+        match stored.remove(&path) {
+            Some(Some(stored_state)) if stored_state == file_state && !full => {
+                if ingest_config.build_overviews && file_kind(filepath) == FileKind::Raster {
+                    overview_candidates.push(entry.path().to_path_buf());
+                }
+                report.add(path, FileStatus::Skipped, None);
+                continue
+            },
+            Some(_) => changed.push(path),
+            None => {}
+        };
+        candidates.push((filepath.to_path_buf(), file_state));
+    }
+    // Rows still left in `stored` belong to files that no longer exist.
+    let vanished: Vec<String> = stored.into_keys().collect();
+    for path in &vanished {
+        report.add(path.clone(), FileStatus::Removed, None);
+    }
+
+    println!("Inspecting {} new or changed files with {} workers....", candidates.len(), ingest_config.workers);
+    let inspection_datadir = datadir.to_path_buf();
+    let inspection_config = ingest_config.clone();
+    let inspected = match tokio::task::spawn_blocking(move || inspect_datasets(&inspection_datadir, candidates, &inspection_config)).await {
+        Ok(inspected) => inspected,
+        Err(_) => {return Err("Datasets could not be inspected")}
+    };
+    if !overview_candidates.is_empty() {
+        println!("Building missing overviews of {} unchanged rasters....", overview_candidates.len());
+        let workers = ingest_config.workers;
+        if let Err(e) = tokio::task::spawn_blocking(move || build_overviews_in_parallel(&overview_candidates, workers)).await {
+            println!("Overviews could not be built: {}", e);
+        }
+    }
+    let mut records: Vec<DatasetRecord> = Vec::with_capacity(inspected.len());
+    let mut failed: HashSet<String> = HashSet::new();
+    for (path, result) in inspected {
+        match result {
+            Ok(file_records) if file_records.is_empty() => report.add(path, FileStatus::Skipped, Some("No raster datasets found".to_string())),
+            Ok(file_records) => {report.add(path, FileStatus::Indexed, None); records.extend(file_records)},
+            Err(e) => {failed.insert(path.clone()); report.add(path, FileStatus::Failed, Some(e))}
+        };
+    }
+    if ingest_config.strict && report.failed > 0 {
+        println!("{} files failed, geo_data was left unchanged", report.failed);
+        return Ok(report);
+    }
+
+    let transaction = match conn.transaction().await {
+        Ok(transaction) => transaction,
+        Err(_) => {return Err("Transaction could not be started")}
+    };
+    match transaction.execute("SELECT pg_advisory_xact_lock($1)", &[&GEO_DATA_LOCK]).await {
+        Ok(_) => {},
+        Err(_) => {return Err("geo_data could not be locked")}
+    };
+    // Changed files that could not be inspected keep their rows until they can be read again.
+    let (kept, changed): (Vec<String>, Vec<String>) = changed.into_iter().partition(|path| failed.contains(path));
+    let removed: Vec<String> = vanished.iter().chain(changed.iter()).cloned().collect();
+    let removed_rows = match transaction.query("DELETE FROM geo_data WHERE COALESCE(file_path, path) = ANY($1) RETURNING layer", &[&removed]).await {
+        Ok(removed_rows) => removed_rows,
+        Err(_) => {return Err("Removed datasets could not be deleted")}
+    };
+    // Without a stored file state the kept rows are inspected again by the next run.
+    match transaction.execute("UPDATE geo_data SET modified = NULL WHERE COALESCE(file_path, path) = ANY($1)", &[&kept]).await {
+        Ok(_) => {},
+        Err(_) => {return Err("Failed datasets could not be marked")}
+    };
+    match insert_records(&transaction, &records).await {
+        Ok(_) => {},
+        Err(e) => {println!("Failed to insert geo_data: {}", e); return Err("Datasets could not be inserted")}
+    };
+    match transaction.batch_execute("DELETE FROM ingest_config").await {
+        Ok(_) => {},
+        Err(_) => {return Err("Ingestion configuration could not be stored")}
+    };
+    match transaction.execute("INSERT INTO ingest_config (fingerprint) VALUES ($1)", &[&fingerprint]).await {
+        Ok(_) => {},
+        Err(_) => {return Err("Ingestion configuration could not be stored")}
+    };
+    match transaction.commit().await {
+        Ok(_) => {},
+        Err(_) => {return Err("Transaction could not be committed")}
+    };
+    report.committed = true;
+    println!("{} indexed, {} skipped, {} failed, {} removed", report.indexed, report.skipped, report.failed, report.removed);
+    if let Some(layer_vrt_dir) = &ingest_config.layer_vrt_dir {
+        // After a full reinspection any layer may have changed.
+        let changed_layers = if full {
+            None
+        } else {
+            let mut changed_layers: Vec<String> = removed_rows.iter().filter_map(|row| row.get::<_, Option<String>>("layer"))
+                .chain(records.iter().map(|record| record.layer.clone()))
+                .collect();
+            changed_layers.sort();
+            changed_layers.dedup();
+            Some(changed_layers)
+        };
+        match build_layer_vrts(&conn, datadir, layer_vrt_dir, changed_layers).await {
+            Ok(_) => {},
+            Err(e) => println!("Layer VRTs could not be built: {}", e)
+        };
+    }
+    Ok(report)
+}
+
+/// Builds a VRT from the paths of its sources.
+///
+/// GDAL opens the sources one after another while building, so layers with more datasets than
+/// open file descriptors can be mosaicked.
+///
+/// # Arguments
+/// * `vrt_path` - Path the VRT is written to.
+/// * `sources` - Paths of the source datasets, later sources are drawn on top.
+/// * `options` - Options of the VRT.
+///
+/// # Returns
+/// * `Ok(())` if the VRT was written.
+/// * `Err(String)` if a path contains a null byte or GDAL could not build the VRT.
+fn build_vrt_from_paths(vrt_path: &Path, sources: &[PathBuf], options: &BuildVRTOptions) -> Result<(), String> {
+    let destination = match CString::new(vrt_path.to_string_lossy().as_bytes()) {
+        Ok(destination) => destination,
+        Err(e) => return Err(format!("Invalid VRT path: {}", e))
+    };
+    let sources: Vec<CString> = match sources.iter().map(|source| CString::new(source.to_string_lossy().as_bytes())).collect() {
+        Ok(sources) => sources,
+        Err(e) => return Err(format!("Invalid dataset path: {}", e))
+    };
+    let source_names: Vec<*const c_char> = sources.iter().map(|source| source.as_ptr()).collect();
+    let mut usage_error: c_int = 0;
+    // SAFETY: the names and options outlive the call, GDAL copies what the returned dataset keeps.
+    // Without dataset handles GDAL opens the sources by name.
+    let vrt = unsafe {
+        gdal_sys::GDALBuildVRT(destination.as_ptr(), source_names.len() as c_int, std::ptr::null_mut(),
+                               source_names.as_ptr(), options.c_options(), &mut usage_error)
+    };
+    if vrt.is_null() {
+        // SAFETY: GDAL returns a null terminated message owned by the current thread.
+        let message = unsafe { CStr::from_ptr(gdal_sys::CPLGetLastErrorMsg()) };
+        return Err(message.to_string_lossy().to_string());
+    }
+    // SAFETY: the dataset was returned by GDALBuildVRT and is closed only here, which writes the VRT.
+    match unsafe { gdal_sys::GDALClose(vrt) } {
+        gdal_sys::CPLErr::CE_None => Ok(()),
+        _ => Err("VRT could not be written".to_string())
+    }
+}
+
+/// Writes a VRT per layer mosaicking all datasets of the layer, the preferred dataset on top.
+///
+/// A VRT is only written if all datasets of the layer share their coordinate system, band count
+/// and vertical datum as stored in geo_data, otherwise GDAL would leave datasets out and reads
+/// would miss them. VRTs of layers that cannot be built or no longer exist are removed, so readers
+/// fall back to the datasets. Point lookups always read the datasets, the VRTs serve grid reads.
+///
+/// # Arguments
+/// * `conn` - Connection to the PostgreSQL database.
+/// * `datadir` - Directory containing geospatial data.
+/// * `layer_vrt_dir` - Directory the VRTs are written to as `<layer>.vrt`.
+/// * `layers` - Layers whose rows changed, `None` rebuilds all of them. Layers without a VRT are built as well.
+///
+/// # Returns
+/// * `Ok(())` if the layers could be queried, failing layers are reported and skipped.
+/// * `Err(String)` if geo_data could not be queried.
+async fn build_layer_vrts(conn: &tokio_postgres::Client, datadir: &Path, layer_vrt_dir: &Path, layers: Option<Vec<String>>) -> Result<(), String> {
+    let rows = match conn.query(
+        &format!("SELECT layer, array_agg(path ORDER BY {}) AS paths, \
+        bool_and(projection IS NOT NULL AND band_count IS NOT NULL AND vertical_datum IS NOT NULL) \
+        AND count(DISTINCT projection) = 1 AND count(DISTINCT band_count) = 1 AND count(DISTINCT vertical_datum) = 1 AS consistent \
+        FROM geo_data WHERE layer IS NOT NULL GROUP BY layer", DATASET_ORDER),
+        &[],
+    ).await {
+        Ok(rows) => rows,
+        Err(e) => return Err(format!("Layers could not be queried: {}", e))
+    };
+    let layer_paths: Vec<(String, Vec<String>, bool)> = rows.iter().map(|row| (row.get("layer"), row.get("paths"), row.get("consistent"))).collect();
+    let datadir = datadir.to_path_buf();
+    let layer_vrt_dir = layer_vrt_dir.to_path_buf();
+    let built = tokio::task::spawn_blocking(move || {
+        if let Err(e) = std::fs::create_dir_all(&layer_vrt_dir) {
+            return Err(format!("{} could not be created: {}", layer_vrt_dir.display(), e));
+        }
+        // Without datasets left the VRT of a layer is stale.
+        let stale: Vec<PathBuf> = WalkDir::new(&layer_vrt_dir).max_depth(1).into_iter()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .filter(|vrt_path| vrt_path.extension().is_some_and(|extension| extension == "vrt"))
+            .filter(|vrt_path| !layer_paths.iter().any(|(layer, _, _)| vrt_path.file_stem().is_some_and(|stem| stem.to_string_lossy() == *layer)))
+            .collect();
+        for vrt_path in stale {
+            if std::fs::remove_file(&vrt_path).is_ok() {
+                println!("Removed {}", vrt_path.display());
+            }
+        }
+        // Datasets of a layer differ in resolution, the VRT keeps the finest one.
+        let options = match BuildVRTOptions::new(["-resolution", "highest"]) {
+            Ok(options) => options,
+            Err(e) => return Err(format!("VRT options could not be created: {}", e))
+        };
+        for (layer, paths, consistent) in layer_paths {
+            let vrt_path = layer_vrt_dir.join(format!("{}.vrt", layer));
+            if vrt_path.is_file() && layers.as_ref().is_some_and(|layers| !layers.contains(&layer)) {
+                continue;
+            }
+            if !consistent {
+                let _ = std::fs::remove_file(&vrt_path);
+                println!("VRT of layer {} not built: its datasets differ in coordinate system, bands or vertical datum", layer);
+                continue;
+            }
+            // Later sources of a VRT are drawn on top, so the preferred dataset goes last.
+            let sources: Vec<PathBuf> = paths.iter().rev().map(|path| dataset_path(&datadir, path)).collect();
+            match build_vrt_from_paths(&vrt_path, &sources, &options) {
+                Ok(_) => println!("Wrote {}", vrt_path.display()),
+                Err(e) => {
+                    let _ = std::fs::remove_file(&vrt_path);
+                    println!("VRT of layer {} could not be built: {}", layer, e)
+                }
+            };
+        }
+        Ok(())
+    }).await;
+    match built {
+        Ok(built) => built,
+        Err(e) => Err(format!("Layer VRTs could not be built: {}", e))
+    }
+}
+
+/// Inspects every indexable file below a directory.
+///
+/// Files that cannot be read are reported and left out, so one broken file does not keep the
+/// rest of a moved or copied directory out of the index.
+///
+/// # Arguments
+/// * `datadir` - Directory containing geospatial data.
+/// * `dirpath` - Path of the directory relative to `datadir`.
+/// * `ingest_config` - Configuration of the dataset ingestion.
+///
+/// # Returns
+/// The rows of all datasets below the directory and the paths of the files that failed.
+fn inspect_directory(datadir: &Path, dirpath: &Path, ingest_config: &IngestConfig) -> (Vec<DatasetRecord>, Vec<String>) {
+    let mut records = Vec::new();
+    let mut failed = Vec::new();
+    for entry in WalkDir::new(datadir.join(dirpath)) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {eprintln!("Directory entry could not be read: {}", e); continue}
+        };
+        if entry.file_type().is_dir() || ingest_config.in_layer_vrt_dir(entry.path()) {
+            continue;
+        }
+        let filepath = match entry.path().strip_prefix(datadir) {
+            Ok(filepath) => filepath,
+            Err(_) => continue
+        };
+        if file_kind(filepath) == FileKind::Other {
+            continue;
+        }
+        let inspected = file_state(entry.path()).and_then(|file_state| inspect_file(datadir, filepath, file_state, ingest_config));
+        match inspected {
+            Ok(file_records) => records.extend(file_records),
+            Err(e) => {eprintln!("{}: {}", filepath.display(), e); failed.push(filepath.to_string_lossy().to_string())}
+        };
+    }
+    (records, failed)
+}
+
+/// Brings the geo_data rows of a single file or directory in line with the file system.
+///
+/// Existing files are inspected and their rows replaced, rows of vanished files are removed.
+/// For a directory this applies to every file below it, so moved directories are reindexed.
+/// Files that cannot be inspected keep their rows.
+///
+/// # Arguments
+/// * `datadir` - Directory containing geospatial data.
+/// * `pool` - The connection pool to the PostgreSQL database.
+/// * `ingest_config` - Configuration of the dataset ingestion.
+/// * `filepath` - Path of the file or directory relative to `datadir`.
+///
+/// # Returns
+/// * `Ok(Vec<Rect<f64>>)` with the bounding boxes of the old and new footprint, whose lookups changed.
+/// * `Err(String)` if the file could not be inspected or geo_data not be updated.
+pub async fn sync_file(datadir: &Path, pool: &ConnectionPool, ingest_config: &IngestConfig, filepath: &Path) -> Result<Vec<Rect<f64>>, String> {
+    let path = filepath.to_string_lossy().to_string();
+    if ingest_config.in_layer_vrt_dir(&datadir.join(filepath)) {
+        return Ok(Vec::new());
+    }
+    let (records, failed) = if datadir.join(filepath).is_dir() {
+        let inspection_datadir = datadir.to_path_buf();
+        let inspection_dirpath = filepath.to_path_buf();
+        let inspection_config = ingest_config.clone();
+        match tokio::task::spawn_blocking(move || inspect_directory(&inspection_datadir, &inspection_dirpath, &inspection_config)).await {
+            Ok(inspected) => inspected,
+            Err(e) => return Err(format!("Directory could not be inspected: {}", e))
+        }
+    } else if datadir.join(filepath).is_file() && file_kind(filepath) != FileKind::Other {
+        let inspection_datadir = datadir.to_path_buf();
+        let inspection_filepath = filepath.to_path_buf();
+        let inspection_config = ingest_config.clone();
+        let inspected = tokio::task::spawn_blocking(move || {
+            let file_state = file_state(&inspection_datadir.join(&inspection_filepath))?;
+            inspect_file(&inspection_datadir, &inspection_filepath, file_state, &inspection_config)
+        }).await;
+        match inspected {
+            Ok(Ok(records)) => (records, Vec::new()),
+            Ok(Err(e)) => return Err(e),
+            Err(e) => return Err(format!("Dataset could not be inspected: {}", e))
+        }
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    let mut conn = match pool.get().await {
+        Ok(conn) => conn,
+        Err(e) => return Err(format!("Database Connection could not be established: {}", e))
+    };
+    let transaction = match conn.transaction().await {
+        Ok(transaction) => transaction,
+        Err(e) => return Err(format!("Transaction could not be started: {}", e))
+    };
+    if let Err(e) = transaction.execute("SELECT pg_advisory_xact_lock($1)", &[&GEO_DATA_LOCK]).await {
+        return Err(format!("geo_data could not be locked: {}", e));
+    }
+    // Rows below the path belong to files of a directory that was moved or removed.
+    let directory_prefix = format!("{}/", path);
+    let rows = match transaction.query(
+        "DELETE FROM geo_data WHERE (COALESCE(file_path, path) = $1 OR starts_with(COALESCE(file_path, path), $2)) \
+        AND NOT COALESCE(file_path, path) = ANY($3) \
+        RETURNING layer, ST_XMin(object) AS min_x, ST_YMin(object) AS min_y, ST_XMax(object) AS max_x, ST_YMax(object) AS max_y",
+        &[&path, &directory_prefix, &failed],
+    ).await {
+        Ok(rows) => rows,
+        Err(e) => return Err(format!("Dataset could not be removed: {}", e))
+    };
+    let mut changed_areas: Vec<Rect<f64>> = rows.iter()
+        .map(|row| Rect::new(coord! {x: row.get::<_, f64>("min_x"), y: row.get::<_, f64>("min_y")}, coord! {x: row.get::<_, f64>("max_x"), y: row.get::<_, f64>("max_y")}))
+        .collect();
+    if let Err(e) = insert_records(&transaction, &records).await {
+        return Err(format!("Failed to insert geo_data: {}", e));
+    }
+    changed_areas.extend(records.iter().filter_map(|record| record.footprint.bounding_rect()));
+    let mut changed_layers: Vec<String> = rows.iter().filter_map(|row| row.get::<_, Option<String>>("layer"))
+        .chain(records.iter().map(|record| record.layer.clone()))
+        .collect();
+    changed_layers.sort();
+    changed_layers.dedup();
+    if let Err(e) = transaction.commit().await {
+        return Err(format!("Transaction could not be committed: {}", e));
+    }
+    if let Some(layer_vrt_dir) = &ingest_config.layer_vrt_dir {
+        if !changed_layers.is_empty() {
+            if let Err(e) = build_layer_vrts(&conn, datadir, layer_vrt_dir, Some(changed_layers)).await {
+                println!("Layer VRTs could not be built: {}", e);
+            }
+        }
+    }
+    Ok(changed_areas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_file_kinds() {
+        assert_eq!(file_kind(Path::new("srtm/N47E008.hgt")), FileKind::Raster);
+        assert_eq!(file_kind(Path::new("srtm/N47E008.HGT")), FileKind::Raster);
+        assert_eq!(file_kind(Path::new("N47E008.tif.gz")), FileKind::Compressed);
+        assert_eq!(file_kind(Path::new("notes.txt.gz")), FileKind::Other);
+        assert_eq!(file_kind(Path::new("tiles.tar.gz")), FileKind::Archive("/vsitar/"));
+        assert_eq!(file_kind(Path::new("N47E008.SRTMGL1.hgt.zip")), FileKind::Archive("/vsizip/"));
+        assert_eq!(file_kind(Path::new("mosaic.vrt")), FileKind::Vrt);
+        assert_eq!(file_kind(Path::new("copernicus.urls")), FileKind::RemoteList);
+        assert_eq!(file_kind(Path::new("N47E008.tif.json")), FileKind::Other);
+    }
+}
@@ -0,0 +1,166 @@
+use axum::{
+    http::StatusCode,
+    Json,
+    extract::{State},
+};
+use axum_macros::debug_handler;
+use geo::{Geometry, HaversineDistance, HaversineIntermediate, LineString, Point};
+use serde::{Deserialize, Serialize};
+use crate::AppState;
+use crate::datum::VerticalDatum;
+use crate::raster::sample_elevations;
+
+/// Mean earth radius in metres.
+pub const EARTH_RADIUS: f64 = 6_371_000.0;
+/// Speed of light in metres per second.
+const SPEED_OF_LIGHT: f64 = 299_792_458.0;
+/// Number of terrain samples taken when the request does not specify one.
+const DEFAULT_SAMPLES: usize = 100;
+/// Upper bound for the number of terrain samples of a single request.
+const MAX_SAMPLES: usize = 1000;
+/// Default effective earth radius factor for standard atmospheric refraction.
+pub const DEFAULT_K_FACTOR: f64 = 4.0 / 3.0;
+
+/// Structure representing one end of a radio link.
+#[derive(Deserialize)]
+struct LinkEndpoint {
+    latitude: f64,
+    longitude: f64,
+    /// Antenna height above ground in metres.
+    #[serde(default)]
+    height: f64,
+}
+
+#[derive(Deserialize)]
+pub struct PostLineOfSight {
+    from: LinkEndpoint,
+    to: LinkEndpoint,
+    /// Number of terrain samples between and including both endpoints.
+    samples: Option<usize>,
+    /// Whether the earth bulge is added to the terrain profile.
+    #[serde(default)]
+    earth_curvature: bool,
+    /// Effective earth radius factor used together with `earth_curvature`.
+    k_factor: Option<f64>,
+    /// Link frequency in MHz, required for the Fresnel zone clearance.
+    frequency: Option<f64>,
+    /// Layer the terrain profile is read from, `$DEFAULTLAYER` if not set.
+    layer: Option<String>,
+    /// Vertical datum of the terrain profile, that of the preferred dataset if not set.
+    datum: Option<VerticalDatum>,
+}
+
+/// Structure representing a sampled point of the terrain profile.
+#[derive(Clone, Serialize)]
+struct ProfilePoint {
+    /// Longitude of the sample.
+    longitude: f64,
+    /// Latitude of the sample.
+    latitude: f64,
+    /// Distance from the first endpoint in metres.
+    distance: f64,
+    /// Terrain elevation of the sample.
+    elevation: f64,
+    /// Vertical distance between the sight line and the (curved) terrain in metres.
+    clearance: f64,
+}
+
+#[derive(Serialize)]
+pub struct LineOfSightResult {
+    /// Whether the sight line clears the terrain.
+    visible: bool,
+    /// Length of the link in metres.
+    distance: f64,
+    /// Sample with the lowest clearance if the sight line is obstructed.
+    obstruction: Option<ProfilePoint>,
+    /// Smallest ratio of clearance to first Fresnel zone radius along the path.
+    fresnel_clearance: Option<f64>,
+    /// Vertical datum of the terrain elevations.
+    vertical_datum: Option<String>,
+    /// Optional error message.
+    error: Option<String>,
+}
+
+impl LineOfSightResult {
+    fn from_error(error: String) -> LineOfSightResult {
+        LineOfSightResult {visible: false, distance: 0f64, obstruction: None, fresnel_clearance: None, vertical_datum: None, error: Some(error)}
+    }
+}
+
+/// Handles POST requests to check the line of sight between two points.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection and cache.
+/// * `payload` - JSON payload containing both endpoints and the profile options.
+///
+/// # Returns
+/// A tuple containing the status code and the JSON result.
+#[debug_handler]
+pub async fn post_line_of_sight(
+    State(appstate): State<AppState>, Json(payload): Json<PostLineOfSight>) -> (StatusCode, Json<LineOfSightResult>) {
+    let samples = payload.samples.unwrap_or(DEFAULT_SAMPLES);
+    if !(2..=MAX_SAMPLES).contains(&samples) {
+        return (StatusCode::BAD_REQUEST, Json(LineOfSightResult::from_error(format!("samples must be between 2 and {}.", MAX_SAMPLES))));
+    }
+    let k_factor = payload.k_factor.unwrap_or(DEFAULT_K_FACTOR);
+    if k_factor <= 0f64 {
+        return (StatusCode::BAD_REQUEST, Json(LineOfSightResult::from_error("k_factor must be positive.".to_string())));
+    }
+    if payload.frequency.is_some_and(|frequency| frequency.is_nan() || frequency <= 0f64) {
+        return (StatusCode::BAD_REQUEST, Json(LineOfSightResult::from_error("frequency must be positive.".to_string())));
+    }
+    let start = Point::new(payload.from.longitude, payload.from.latitude);
+    let end = Point::new(payload.to.longitude, payload.to.latitude);
+    let distance = start.haversine_distance(&end);
+    if distance == 0f64 {
+        return (StatusCode::BAD_REQUEST, Json(LineOfSightResult::from_error("from and to must be different locations.".to_string())));
+    }
+    let points: Vec<Point> = (0..samples)
+        .map(|index| start.haversine_intermediate(&end, index as f64 / (samples - 1) as f64))
+        .collect();
+
+    let layer = payload.layer.clone().unwrap_or_else(|| appstate.ingest_config.default_layer.clone());
+    let path = Geometry::from(LineString::from(points.clone()));
+    let coordinates: Vec<(f64, f64)> = points.iter().map(|point| (point.x(), point.y())).collect();
+    let (sampled, vertical_datum) = match sample_elevations(&appstate, &path, &layer, payload.datum, coordinates).await {
+        Ok(sampled) => sampled,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(LineOfSightResult::from_error(e)))
+    };
+    let mut elevations: Vec<f64> = Vec::with_capacity(sampled.len());
+    for (point, elevation) in points.iter().zip(sampled) {
+        match elevation {
+            Some(elevation) => elevations.push(elevation),
+            None => return (StatusCode::OK, Json(LineOfSightResult::from_error(format!("No such coordinate {} {}.", point.y(), point.x()))))
+        };
+    }
+    let height_start = elevations[0] + payload.from.height;
+    let height_end = elevations[elevations.len() - 1] + payload.to.height;
+    let wavelength = payload.frequency.map(|frequency| SPEED_OF_LIGHT / (frequency * 1e6));
+
+    let mut obstruction: Option<ProfilePoint> = None;
+    let mut fresnel_clearance: Option<f64> = None;
+    // The endpoints carry the antennas themselves, so only the path in between can obstruct.
+    for (index, point) in points.iter().enumerate().take(points.len() - 1).skip(1) {
+        let distance_start = distance * index as f64 / (samples - 1) as f64;
+        let distance_end = distance - distance_start;
+        let sight_line = height_start + (height_end - height_start) * distance_start / distance;
+        let earth_bulge = if payload.earth_curvature {
+            distance_start * distance_end / (2f64 * k_factor * EARTH_RADIUS)
+        } else {
+            0f64
+        };
+        let clearance = sight_line - (elevations[index] + earth_bulge);
+        if clearance < 0f64 && obstruction.as_ref().is_none_or(|worst| clearance < worst.clearance) {
+            obstruction = Some(ProfilePoint {longitude: point.x(), latitude: point.y(), distance: distance_start, elevation: elevations[index], clearance});
+        }
+        if let Some(wavelength) = wavelength {
+            let fresnel_radius = (wavelength * distance_start * distance_end / distance).sqrt();
+            if fresnel_radius > 0f64 {
+                let ratio = clearance / fresnel_radius;
+                fresnel_clearance = Some(fresnel_clearance.map_or(ratio, |smallest: f64| smallest.min(ratio)));
+            }
+        }
+    }
+    (StatusCode::OK, Json(LineOfSightResult {visible: obstruction.is_none(), distance, obstruction, fresnel_clearance,
+        vertical_datum: vertical_datum.map(|datum| datum.as_str().to_string()), error: None}))
+}
@@ -1,12 +1,8 @@
-use gdal::{Dataset};
+use gdal::{Dataset, GeoTransformEx};
 use gdal::raster::RasterBand;
-use gdal::spatial_ref::{SpatialRef, CoordTransform};
-use std::path::Path;
-use geozero::wkb;
-use geo::{Geometry, Polygon};
-use walkdir::WalkDir;
-use clap::Parser;
-use exitcode;
+use gdal::spatial_ref::CoordTransform;
+use std::path::{Path, PathBuf};
+use clap::{Args, FromArgMatches, Parser, Subcommand};
 use std::env;
 use axum::{
     routing::{post},
@@ -15,19 +11,198 @@ use axum::{
     extract::{State},
 };
 use axum::routing::get;
+use axum::extract::DefaultBodyLimit;
+use axum::middleware;
+use axum::response::{IntoResponse, Response};
 use axum_macros::debug_handler;
 use bb8::{Pool};
 use bb8_postgres::PostgresConnectionManager;
 use tokio_postgres::{NoTls};
+use tokio_postgres::types::ToSql;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
 use moka::future::Cache;
+use datum::{GeoidModels, VerticalDatum};
+use ingest::{parse_data_create_database, IngestConfig};
+use raster::DATASET_ORDER;
+
+mod admin;
+mod cache_warm;
+mod catalog;
+mod coverage;
+mod datum;
+mod encodings;
+mod export;
+mod ingest;
+mod line_of_sight;
+mod raster;
+mod validate;
+mod viewshed;
+mod watch;
+mod zonal_statistics;
+
+/// Time `validate` waits for the database before skipping the overlap checks.
+const VALIDATE_DB_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
-    #[arg(short, long)]
-    regenerate: bool,
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Configuration shared by all subcommands, read from the environment if not given.
+#[derive(Args)]
+struct ConfigArgs {
+    /// User of the PostgreSQL database.
+    #[arg(long, env = "DBUSER", global = true)]
+    db_user: Option<String>,
+    /// Password of the PostgreSQL database.
+    #[arg(long, env = "DBPASS", global = true, hide_env_values = true)]
+    db_pass: Option<String>,
+    /// Host of the PostgreSQL database.
+    #[arg(long, env = "DBHOST", global = true)]
+    db_host: Option<String>,
+    /// Name of the PostgreSQL database.
+    #[arg(long, env = "DBDATABASE", global = true)]
+    db_database: Option<String>,
+    /// Directory containing geospatial data.
+    #[arg(long, env = "DATADIR", global = true)]
+    datadir: Option<String>,
+    /// Path of the lookup endpoint.
+    #[arg(long, env = "APIURL", global = true)]
+    api_url: Option<String>,
 }
+
+/// Options of synchronising the index with the data directory.
+#[derive(Args)]
+struct IndexArgs {
+    /// Reinspect every dataset instead of only new and changed files.
+    #[arg(long)]
+    full: bool,
+    /// Number of threads inspecting datasets, defaults to the number of CPUs.
+    #[arg(long)]
+    workers: Option<usize>,
+    /// Leave the index unchanged if any dataset fails to be indexed.
+    #[arg(long)]
+    strict: bool,
+    /// Write a JSON report with the outcome of every file to this path.
+    #[arg(long)]
+    report: Option<String>,
+    /// Build overviews for rasters without overviews.
+    #[arg(long)]
+    build_overviews: bool,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// Synchronise the index with the data directory before serving.
+    #[arg(long)]
+    index: bool,
+    #[command(flatten)]
+    index_args: IndexArgs,
+    /// Watch the data directory while serving and update the index on changes.
+    #[arg(long)]
+    watch: bool,
+    /// Number of lookups kept in the cache.
+    #[arg(long, env = "CACHESIZE")]
+    cache_size: Option<u64>,
+    /// Address the server listens on.
+    #[arg(long, default_value = "0.0.0.0:3000")]
+    listen: String,
+}
+
+/// Options of lookups from the terminal.
+#[derive(Args)]
+struct LookupArgs {
+    /// Vertical datum of the returned elevations.
+    #[arg(long, value_parser = parse_datum)]
+    datum: Option<VerticalDatum>,
+    /// Layer to look up, repeat for several layers.
+    #[arg(long = "layer")]
+    layers: Vec<String>,
+    /// Buffer distance in metres for blending overlapping datasets.
+    #[arg(long)]
+    blend: Option<f64>,
+    /// Target ground resolution in metres.
+    #[arg(long)]
+    resolution: Option<f64>,
+    /// Return the metadata of the dataset with each result.
+    #[arg(long)]
+    metadata: bool,
+    /// EPSG code of the coordinates, EPSG 4326 if not set.
+    #[arg(long)]
+    srs: Option<u32>,
+}
+
+impl LookupArgs {
+    fn options(&self) -> LookupOptions {
+        LookupOptions {datum: self.datum, layers: self.layers.clone(), blend: self.blend, resolution: self.resolution, metadata: self.metadata}
+    }
+}
+
+/// Parses a vertical datum argument.
+fn parse_datum(name: &str) -> Result<VerticalDatum, String> {
+    VerticalDatum::from_name(name).ok_or(format!("Unknown datum {}.", name))
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the API, the default if no subcommand is given.
+    Serve(ServeArgs),
+    /// Synchronise the index with the data directory, incrementally unless `--full` is given.
+    Index(IndexArgs),
+    /// Check rasters before ingesting them and print a report without changing the index.
+    Validate {
+        /// A raster or a directory of rasters.
+        path: PathBuf,
+        /// Write the report as JSON to this path.
+        #[arg(long)]
+        report: Option<String>,
+    },
+    /// Look up locations and print the results as JSON.
+    Lookup {
+        /// Locations as `latitude,longitude` or location identifier, after all options since
+        /// negative latitudes start with a hyphen.
+        #[arg(required = true, allow_hyphen_values = true)]
+        locations: Vec<String>,
+        #[command(flatten)]
+        lookup: LookupArgs,
+    },
+    /// Print the area covered by indexed datasets as GeoJSON.
+    Coverage {
+        /// Only consider datasets of this layer.
+        #[arg(long)]
+        layer: Option<String>,
+        /// Comma separated list of `layer` and `resolution`, one feature is printed per group.
+        #[arg(long)]
+        group_by: Option<String>,
+        /// Comma separated ascending upper bounds in metres of the ground resolution classes.
+        #[arg(long, value_delimiter = ',')]
+        classes: Vec<f64>,
+        /// Web map zoom level the footprints are simplified for.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(0..=coverage::MAX_ZOOM as i64))]
+        zoom: Option<u32>,
+    },
+    /// Fill the lookup cache of a running server with the locations of a file.
+    CacheWarm {
+        /// File with one `latitude,longitude` pair or location identifier per line, `-` for stdin.
+        file: PathBuf,
+        /// Base URL of the running server.
+        #[arg(long, default_value = "http://127.0.0.1:3000")]
+        server: String,
+        /// Number of locations per request.
+        #[arg(long, default_value_t = 100)]
+        batch_size: usize,
+        #[command(flatten)]
+        lookup: LookupArgs,
+    },
+}
+
 /// Structure representing a coordinate result.
 #[derive(Clone, Serialize)]
 struct CoordinateResult {
@@ -37,10 +212,155 @@ struct CoordinateResult {
     latitude: f64,
     /// Elevation of the coordinate.
     elevation: i32,
+    /// Vertical datum of the elevation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vertical_datum: Option<String>,
+    /// Location identifier the coordinate was decoded from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    /// Layer of the dataset the elevation was read from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    layer: Option<String>,
+    /// Ground resolution in metres of the dataset the elevation was read from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ground_resolution: Option<f64>,
+    /// Elevations of all requested layers if more than one layer was requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    layers: Option<Vec<LayerElevation>>,
+    /// Values of the additional bands configured for the layer, e.g. vertical accuracy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bands: Option<BTreeMap<String, f64>>,
+    /// Metadata of the dataset the elevation was read from, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<DatasetMetadata>,
     /// Optional error message.
     error: Option<String>,
 }
 
+impl CoordinateResult {
+    fn from_error(lat: f64, lon: f64, error: String) -> CoordinateResult {
+        CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, vertical_datum: None, location: None, layer: None, ground_resolution: None, layers: None, bands: None, metadata: None, error: Some(error)}
+    }
+}
+
+/// Structure representing the elevation of one layer at a coordinate.
+#[derive(Clone, Serialize)]
+struct LayerElevation {
+    layer: String,
+    elevation: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vertical_datum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bands: Option<BTreeMap<String, f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<DatasetMetadata>,
+    error: Option<String>,
+}
+
+/// Metadata of a dataset as stored in geo_data at ingest time.
+#[derive(Clone, Serialize)]
+struct DatasetMetadata {
+    /// Name of the dataset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dataset: Option<String>,
+    /// Acquisition date as `YYYY-MM-DD`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acquisition_date: Option<String>,
+    /// Ground resolution in metres.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ground_resolution: Option<f64>,
+    /// Horizontal coordinate system of the dataset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    horizontal_crs: Option<String>,
+    /// Vertical coordinate system of the dataset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vertical_crs: Option<String>,
+    /// Stated vertical accuracy in metres.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vertical_accuracy: Option<f64>,
+}
+
+impl DatasetMetadata {
+    /// Reads the metadata from a geo_data row.
+    fn from_row(row: &tokio_postgres::Row) -> DatasetMetadata {
+        DatasetMetadata {
+            dataset: row.get("dataset_name"),
+            acquisition_date: row.get("acquisition_date"),
+            ground_resolution: row.get("ground_resolution"),
+            horizontal_crs: row.get("horizontal_crs"),
+            vertical_crs: row.get("vertical_crs"),
+            vertical_accuracy: row.get("vertical_accuracy"),
+        }
+    }
+}
+
+/// Band configuration of the datasets of a layer.
+#[derive(Clone, Deserialize)]
+struct LayerBands {
+    /// Band holding the elevation.
+    #[serde(default = "default_elevation_band")]
+    elevation: usize,
+    /// Additional bands returned with the elevation, by name.
+    #[serde(default)]
+    bands: BTreeMap<String, usize>,
+}
+
+fn default_elevation_band() -> usize {
+    1
+}
+
+/// Reads the band configuration of the layers from `$LAYERBANDS`.
+///
+/// The variable holds a JSON object keyed by layer, e.g.
+/// `{"lidar": {"elevation": 1, "bands": {"vertical_accuracy": 2}}}`. Layers without an entry
+/// read the elevation from the first band.
+///
+/// # Returns
+/// * `Ok(HashMap<String, LayerBands>)` with the configuration of every configured layer.
+/// * `Err(String)` if the variable is not valid.
+fn layer_bands_from_env() -> Result<HashMap<String, LayerBands>, String> {
+    let layer_bands: HashMap<String, LayerBands> = match env::var("LAYERBANDS") {
+        Ok(value) => match serde_json::from_str(&value) {
+            Ok(layer_bands) => layer_bands,
+            Err(e) => return Err(format!("LAYERBANDS is not valid: {}", e))
+        },
+        Err(_) => HashMap::new()
+    };
+    for (layer, config) in &layer_bands {
+        if config.elevation == 0 || config.bands.values().any(|band| *band == 0) {
+            return Err(format!("LAYERBANDS of layer {} contains band 0, bands are counted from 1", layer));
+        }
+    }
+    Ok(layer_bands)
+}
+
+/// Returns the band holding the elevation in the datasets of a layer, the first band if the layer is not configured.
+fn elevation_band(layer_bands: &HashMap<String, LayerBands>, layer: Option<&str>) -> usize {
+    layer.and_then(|layer| layer_bands.get(layer)).map_or(1, |layer_bands| layer_bands.elevation)
+}
+
+/// Options of a lookup that change its result.
+#[derive(Clone, Default)]
+struct LookupOptions {
+    /// Vertical datum of the returned elevation, the native datum of the dataset if not set.
+    datum: Option<VerticalDatum>,
+    /// Layers to look up, datasets of all layers are considered if empty.
+    layers: Vec<String>,
+    /// Buffer distance in metres inside footprint edges across which overlapping datasets are blended.
+    blend: Option<f64>,
+    /// Target ground resolution in metres, datasets are read from the matching overview.
+    resolution: Option<f64>,
+    /// Whether the metadata of the preferred dataset is returned.
+    metadata: bool,
+}
+
+impl LookupOptions {
+    /// Returns the cache key of a coordinate looked up with these options.
+    fn cache_key(&self, lat: f64, lon: f64) -> String {
+        format!("{},{},{},{},{},{},{}", lat, lon, self.datum.map_or("", |datum| datum.as_str()), self.layers.join("|"), self.blend.unwrap_or(0f64), self.resolution.unwrap_or(0f64), self.metadata)
+    }
+}
+
 #[derive(Serialize)]
 struct CoordinateResultList {
     results: Vec<CoordinateResult>,
@@ -48,12 +368,49 @@ struct CoordinateResultList {
 #[derive(Deserialize)]
 struct PostCoordinates {
     locations: Vec<CoordinateRequests>,
+    datum: Option<VerticalDatum>,
+    /// Layer to look up.
+    layer: Option<String>,
+    /// Layers to look up, one elevation is returned per layer.
+    layers: Option<Vec<String>>,
+    /// Buffer distance in metres for blending overlapping datasets.
+    blend: Option<f64>,
+    /// Target ground resolution in metres.
+    resolution: Option<f64>,
+    /// Whether the metadata of the dataset is returned with each result.
+    metadata: Option<bool>,
+    /// EPSG code of the locations, EPSG 4326 if not set.
+    #[serde(alias = "crs")]
+    srs: Option<u32>,
 }
 #[derive(Deserialize)]
 struct CoordinateRequests {
-    latitude: f64,
-    longitude: f64,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    /// MGRS grid reference, geohash or plus code used instead of latitude and longitude.
+    location: Option<String>,
+}
+
+/// A location of a lookup request.
+enum Location {
+    /// Latitude and longitude, or northing and easting in the input coordinate system.
+    Coordinate(f64, f64),
+    /// MGRS grid reference, geohash or plus code.
+    Encoded(String),
+    /// Location that could not be parsed.
+    Invalid(String),
+}
+/// Structure representing an error of an endpoint without a result list.
+#[derive(Serialize)]
+struct ErrorResult {
+    error: String,
+}
+
+/// Returns an error response with a JSON body.
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(ErrorResult {error})).into_response()
 }
+
 /// Application state structure shared across handlers.
 #[derive(Clone)]
 struct AppState {
@@ -63,226 +420,438 @@ struct AppState {
     datadir: String,
     /// Cache for storing previously looked-up coordinates.
     cache: Cache<String, CoordinateResult>,
+    /// Geoid grids for vertical datum conversion.
+    geoids: GeoidModels,
+    /// Band configuration by layer.
+    layer_bands: Arc<HashMap<String, LayerBands>>,
+    /// Configuration of the dataset ingestion, used when datasets change while serving.
+    ingest_config: Arc<IngestConfig>,
+    /// Bearer token of the admin API, read from `$ADMINTOKEN`.
+    admin_token: Option<String>,
 }
 
 type ConnectionPool = Pool<PostgresConnectionManager<NoTls>>;
 
-
-/// Parses geospatial data from the specified directory and creates a database.
+/// Selects the coarsest overview of a band that is still at least as fine as a target resolution.
 ///
 /// # Arguments
-/// * `datadir_path_string` - The path to the directory containing the geospatial data.
-/// * `pool` - The connection pool to the PostgreSQL database.
+/// * `rasterband` - The full resolution band.
+/// * `ground_resolution` - Ground resolution of the full resolution band in metres.
+/// * `target_resolution` - Requested ground resolution in metres.
 ///
 /// # Returns
-/// * `Ok(true)` if the process is successful.
-/// * `Err(&str)` if the database could not be created or data could not be processed.
-async fn parse_data_create_database(datadir_path_string: String, pool: ConnectionPool) -> Result<bool, &'static str>{
-    let datadir = Path::new(datadir_path_string.as_str());
-    let conn = match pool.get().await {
-        Ok(conn) => conn,
-        Err(_) => {return Err("Database Connection could not be established")}
-    };
-
-    let _ = match conn.batch_execute("DROP TABLE geo_data").await {
-        Ok(_) => {},
-        Err(_) => {println!("Old Database could not be dropped. Continuing...")}
-    };
-    let _ = match conn.batch_execute("
-        CREATE TABLE IF NOT EXISTS geo_data (
-            id              SERIAL PRIMARY KEY,
-            path            VARCHAR,
-            resolution      INTEGER,
-            object          GEOMETRY
-            )
-    ").await {
-        Ok(_) => {},
-        Err(_) => {return Err("New Database could not be created")}
-    };
-    println!("Walking Directory....");
-    for entry in WalkDir::new(datadir)
-        .into_iter()
-        .filter_map(Result::ok)
-        .filter(|e| !e.file_type().is_dir()) {
-        let filepath = match entry.path().strip_prefix(datadir){
-            Ok(filepath) => filepath,
-            Err(e) => {println!("Filepath could not be formated: {}", e); continue}
-        };
-        println!("{}", filepath.to_str().unwrap());
-        let dataset = match Dataset::open(datadir.join(filepath)){
-            Ok(dataset) => dataset,
-            Err(e) => {println!("Dataset could not be opened: {}", e); continue}
-        };
-        println!("{:?}", dataset.projection());
-        let projection_string = dataset.projection();
-        let collection = projection_string.split("EPSG\",").collect::<Vec<&str>>();
-        let epsg_string: &&str = match collection.last(){
-            Some(epsg_string) => epsg_string,
-            None => {println!("EPSG String could not be parsed"); continue}
+/// The selected overview, or the band itself if no overview is coarse enough.
+fn select_overview<'a>(rasterband: RasterBand<'a>, ground_resolution: f64, target_resolution: f64) -> RasterBand<'a> {
+    let (width, _) = rasterband.size();
+    let mut selected: Option<(f64, RasterBand<'a>)> = None;
+    for index in 0..rasterband.overview_count().unwrap_or(0).max(0) as usize {
+        let overview = match rasterband.overview(index) {
+            Ok(overview) => overview,
+            Err(_) => continue
         };
-        let epsg_number = match (&epsg_string[1..epsg_string.len() - 3]).parse::<i32>() {
-            Ok(epsg_number) => epsg_number,
-            Err(e) => {println!("EPSG Number could not be parsed: {}", e); continue}
+        let overview_resolution = ground_resolution * width as f64 / overview.size().0.max(1) as f64;
+        let coarser = match &selected {
+            Some((resolution, _)) => overview_resolution > *resolution,
+            None => true
         };
-        println!("{:?}", epsg_number);
-        let spat = match SpatialRef::from_esri(&*dataset.projection()) {
-            Ok(spatial_ref) => spatial_ref,
-            Err(e) => {println!("Source SpatialRef could not be parsed: {}", e); continue}
-        };
-        let spat_target = match SpatialRef::from_epsg(4326) {
-            Ok(spat_target_ref) => spat_target_ref,
-            Err(e) => {println!("Target SpatialRef could not be parsed: {}", e); continue}
-        };
-        let geo = match CoordTransform::new(&spat, &spat_target) {
-            Ok(geo) => geo,
-            Err(e) => {println!("CoordTransform could not be created: {}", e); continue}
-        };
-        let (width, height) = dataset.raster_size();
-        let geotransform = match dataset.geo_transform() {
-            Ok(geotransform) => geotransform,
-            Err(e) => {println!("Geo transform could not be created: {}", e); continue}
-        };
-        let mut x_coord = [geotransform[0], geotransform[0] + width as f64 * geotransform[1] + height as f64 * geotransform[2]];
-        let mut y_coord = [geotransform[3] + width as f64 * geotransform[4] + height as f64 * geotransform[5],  geotransform[3]];
-        match geo.transform_coords(&mut x_coord, &mut y_coord, &mut [0.0, 0.0]) {
-            Ok(_) => {},
-            Err(e) => {println!("Transform coords could not be converted: {}", e); continue}
-        };
-        println!("{:?}, {:?}, {:?}, {:?}",geotransform[0], geotransform[3] + width as f64 * geotransform[4] + height as f64 * geotransform[5], geotransform[0] + width as f64 * geotransform[1] + height as f64 * geotransform[2], geotransform[3]);
-        if epsg_number == 25832 {
-            //We need to make a special exception for EPSG 25832 because Lat/Lon is switched in the conversion
-            (x_coord[0], x_coord[1], y_coord[0], y_coord[1]) = (y_coord[0], y_coord[1], x_coord[0], x_coord[1]);
+        if overview_resolution <= target_resolution && coarser {
+            selected = Some((overview_resolution, overview));
         }
-        println!("{:?}, {:?} | {:?}, {:?} | {:?}, {:?} | {:?}, {:?}", x_coord[0], y_coord[0], x_coord[0], y_coord[1], x_coord[1], y_coord[1],  x_coord[1], y_coord[0]);
-        let resolution = (width as f64 / ((500f64 + x_coord[0]) - (500f64 + x_coord[1])).abs()) as i32;
-        let coord_1 = geo::Coord::from((x_coord[0], y_coord[0]));
-        let coord_2 = geo::Coord::from((x_coord[0], y_coord[1]));
-        let coord_3 = geo::Coord::from((x_coord[1], y_coord[1]));
-        let coord_4 = geo::Coord::from((x_coord[1], y_coord[0]));
-        let geom: Geometry<f64> = Polygon::new(geo::LineString(vec![coord_1, coord_2, coord_3, coord_4]), vec![]).into();
-        let _ = match conn.execute("INSERT INTO geo_data (path,resolution,object) VALUES($1, $2, ST_SetSRID(CAST ($3 AS geometry),4326))",
-                               &[&filepath.to_str().unwrap(), &resolution, &wkb::Encode(geom)]).await {
-            Ok(_) => {},
-            Err(e) => {println!("Failed to insert geo_data: {}", e); continue}
-        };
     }
-    Ok(true)
+    selected.map_or(rasterband, |(_, overview)| overview)
+}
+
+/// Reads a single pixel of a band, from the matching overview if a target resolution is set.
+///
+/// # Arguments
+/// * `dataset` - The opened dataset.
+/// * `band_index` - Index of the band, starting at 1.
+/// * `pixel` - Pixel coordinates in the full resolution raster.
+/// * `resolutions` - Ground resolution of the dataset and target resolution in metres.
+///
+/// # Returns
+/// * `Ok((f64, Option<f64>))` with the pixel value and the NoData value of the band.
+/// * `Err(String)` if the band could not be read.
+fn read_band_pixel(dataset: &Dataset, band_index: usize, pixel: (f64, f64), resolutions: (Option<f64>, Option<f64>)) -> Result<(f64, Option<f64>), String> {
+    let (width, height) = dataset.raster_size();
+    let rasterband: RasterBand = match dataset.rasterband(band_index) {
+        Ok(rasterband) => rasterband,
+        Err(e) => return Err(format!("Rasterband {} could not be opened: {}", band_index, e))
+    };
+    let no_data = rasterband.no_data_value();
+    let rasterband = match resolutions {
+        (Some(ground_resolution), Some(target_resolution)) => select_overview(rasterband, ground_resolution, target_resolution),
+        _ => rasterband
+    };
+    let (pixel_x, pixel_y) = pixel;
+    let (band_width, band_height) = rasterband.size();
+    let (pixel_x, pixel_y) = if (band_width, band_height) == (width, height) {
+        (pixel_x, pixel_y)
+    } else {
+        ((pixel_x * band_width as f64 / width as f64).floor(), (pixel_y * band_height as f64 / height as f64).floor())
+    };
+    match rasterband.read_as::<f64>((pixel_x as isize, pixel_y as isize), (1, 1), (1, 1), None) {
+        Ok(rv) => Ok((rv.data()[0], no_data)),
+        Err(e) => Err(format!("Raster band {} could not be read: {}", band_index, e))
+    }
 }
 
+/// Elevation at a pixel with the values of the additional bands by name.
+type PixelValues = (f64, BTreeMap<String, f64>);
+
+/// Reads the elevation and the configured additional band values at a coordinate from a single dataset.
+///
+/// # Arguments
+/// * `path` - Full path of the dataset.
+/// * `lat` - Latitude of the point.
+/// * `lon` - Longitude of the point.
+/// * `resolutions` - Ground resolution of the dataset and target resolution in metres, both required to read from an overview.
+/// * `layer_bands` - Band configuration of the layer of the dataset, the elevation is read from the first band if not set.
+///
+/// # Returns
+/// * `Ok(Some(PixelValues))` with the elevation and the additional band values at the coordinate.
+///   Additional bands that hold NoData or could not be read are left out.
+/// * `Ok(None)` if the coordinate lies outside the raster or the elevation band holds NoData at it.
+/// * `Err(String)` if the dataset could not be read.
+fn read_dataset_elevation(path: &Path, lat: f64, lon: f64, resolutions: (Option<f64>, Option<f64>), layer_bands: Option<&LayerBands>) -> Result<Option<PixelValues>, String> {
+    let dataset = match Dataset::open(path) {
+        Ok(dataset) => dataset,
+        Err(e) => return Err(format!("Dataset could not be opened: {}", e))
+    };
+    let transform = raster::wgs84_to_dataset(&dataset)?;
+    let inverse = match dataset.geo_transform().and_then(|geotransform| geotransform.invert()) {
+        Ok(inverse) => inverse,
+        Err(e) => return Err(format!("Geo transform could not be inverted: {}", e))
+    };
+    let mut x_coord = [lon];
+    let mut y_coord = [lat];
+    if let Err(e) = transform.transform_coords(&mut x_coord, &mut y_coord, &mut [0.0]) {
+        return Err(format!("Transform coords could not be converted: {}", e));
+    }
+    let (pixel_x, pixel_y) = inverse.apply(x_coord[0], y_coord[0]);
+    let (width, height) = dataset.raster_size();
+    // Footprints in geo_data are approximate, a point just outside the raster has no value in it.
+    if pixel_x < 0f64 || pixel_y < 0f64 || pixel_x >= width as f64 || pixel_y >= height as f64 {
+        return Ok(None);
+    }
+    let (pixel_x, pixel_y) = (pixel_x.floor(), pixel_y.floor());
+    let elevation_band = layer_bands.map_or(1, |layer_bands| layer_bands.elevation);
+    let (elevation, no_data) = read_band_pixel(&dataset, elevation_band, (pixel_x, pixel_y), resolutions)?;
+    if raster::is_no_data(elevation, no_data) {
+        return Ok(None);
+    }
+    let mut band_values: BTreeMap<String, f64> = BTreeMap::new();
+    if let Some(layer_bands) = layer_bands {
+        for (name, band_index) in &layer_bands.bands {
+            match read_band_pixel(&dataset, *band_index, (pixel_x, pixel_y), resolutions) {
+                Ok((value, no_data)) if raster::is_no_data(value, no_data) => {},
+                Ok((value, _)) => {band_values.insert(name.clone(), value);},
+                Err(e) => eprintln!("{}", e)
+            };
+        }
+    }
+    Ok(Some((elevation, band_values)))
+}
 
 /// Looks up elevation data based on latitude and longitude.
 ///
+/// The preferred dataset holding a value at the point provides the elevation, datasets with
+/// NoData at the point are passed over. With blending enabled,
+/// each dataset is weighted by the distance of the point to the edge of the coverage of its layer
+/// with the same or a higher priority relative to the blend buffer, and the remaining weight is
+/// passed on to the next dataset containing the point.
+///
 /// # Arguments
 /// * `lat` - Latitude of the point.
 /// * `lon` - Longitude of the point.
-/// * `pool` - PostgreSQL connection pool.
-/// * `config_datadir` - Directory containing geospatial data.
+/// * `options` - Options of the lookup.
+/// * `appstate` - Application state containing the database connection and geoid grids.
 ///
 /// # Returns
 /// A `CoordinateResult` containing the elevation or an error message.
-async fn lookup_coordinats(lat: f64, lon: f64, pool: &ConnectionPool, config_datadir: &str) -> CoordinateResult {
-    let conn = match pool.get().await {
+async fn lookup_coordinats(lat: f64, lon: f64, options: &LookupOptions, appstate: &AppState) -> CoordinateResult {
+    let conn = match appstate.db_connection.get().await {
         Ok(conn) => conn,
         Err(_e) => {
             eprintln!("{:?}", _e);
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from("Internal Server Error".to_string())};
+            return CoordinateResult::from_error(lat, lon, "Internal Server Error".to_string());
         }
     };
-    let datadir = Path::new(config_datadir);
-    let row = match conn.query(
-        &format!("SELECT * FROM geo_data WHERE ST_Contains(object, ST_GeomFromText('POINT({} {})', 4326)) ORDER BY resolution DESC;", lon, lat),
-        &[],
-    ).await {
-        Ok(row) => row,
-        Err(_e) => {
-            eprintln!("{:?}", _e);
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("No such coordinate {} {}.", lat, lon))};
-        }
+    let datadir = Path::new(&appstate.datadir);
+    let mut params: Vec<&(dyn ToSql + Sync)> = vec![&lon, &lat];
+    // The edge is that of the coverage of the layer with the same or a higher priority, so seams
+    // between adjacent tiles are not feathered. Only footprints within the buffer can shape it.
+    let edge_distance = match &options.blend {
+        Some(buffer) if *buffer > 0f64 => {
+            params.push(buffer);
+            format!(", ST_Distance(ST_Boundary((SELECT ST_Union(coverage.object) FROM geo_data coverage \
+                WHERE coverage.layer IS NOT DISTINCT FROM geo_data.layer \
+                AND COALESCE(coverage.priority, 0) >= COALESCE(geo_data.priority, 0) \
+                AND ST_DWithin(coverage.object::geography, ST_SetSRID(ST_MakePoint($1, $2), 4326)::geography, ${})))::geography, \
+                ST_SetSRID(ST_MakePoint($1, $2), 4326)::geography) AS edge_distance", params.len())
+        },
+        _ => String::new()
     };
-    if row.len() == 0 {
-        return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("No such coordinate {} {}.", lat, lon))};
-    }
-    let value: String = row[0].get("path");
-    let dataset = match Dataset::open(datadir.join(Path::new(&value))) {
-        Ok(dataset) => dataset,
-        Err(_e) => {
-            eprintln!("{:?}", _e);
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("Internal Server Error {} {}.", lat, lon))};
-        }
+    let layer_filter = match options.layers.first() {
+        Some(layer) => {
+            params.push(layer);
+            format!(" AND layer = ${}", params.len())
+        },
+        None => String::new()
     };
-    let spat_point = match SpatialRef::from_epsg(4326) {
-        Ok(spatial_ref) => spatial_ref,
+    let query = format!("SELECT *{} FROM geo_data WHERE ST_Contains(object, ST_SetSRID(ST_MakePoint($1, $2), 4326)){} ORDER BY {};", edge_distance, layer_filter, DATASET_ORDER);
+    let row = conn.query(&query, &params).await;
+    let row = match row {
+        Ok(row) => row,
         Err(_e) => {
             eprintln!("{:?}", _e);
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("Internal Server Error {} {}.", lat, lon))};
+            return CoordinateResult::from_error(lat, lon, format!("No such coordinate {} {}.", lat, lon));
         }
     };
-    let spat_data = match SpatialRef::from_esri(&*dataset.projection()) {
-        Ok(spatial_ref) => spatial_ref,
-        Err(_e) => {
-            eprintln!("{:?}", _e);
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("Internal Server Error {} {}.", lat, lon))};
+    if row.is_empty() {
+        return CoordinateResult::from_error(lat, lon, format!("No such coordinate {} {}.", lat, lon));
+    }
+    let mut preferred: Option<(&tokio_postgres::Row, f64)> = None;
+    let mut native_datum: Option<VerticalDatum> = None;
+    let mut band_values: BTreeMap<String, f64> = BTreeMap::new();
+    let mut weighted_elevation = 0f64;
+    let mut remaining_weight = 1f64;
+    for (index, dataset_row) in row.iter().enumerate() {
+        let value: String = dataset_row.get("path");
+        let resolutions = (dataset_row.get::<_, Option<f64>>("ground_resolution"), options.resolution);
+        let layer_bands = dataset_row.get::<_, Option<String>>("layer").and_then(|layer| appstate.layer_bands.get(&layer));
+        let row_datum = dataset_row.get::<_, Option<String>>("vertical_datum").as_deref().and_then(VerticalDatum::from_name);
+        let elevation = match read_dataset_elevation(&raster::dataset_path(datadir, &value), lat, lon, resolutions, layer_bands) {
+            // Layer, datum, metadata and additional band values are taken from the preferred dataset only.
+            Ok(Some((elevation, values))) => {
+                if preferred.is_none() {
+                    preferred = Some((dataset_row, elevation));
+                    native_datum = row_datum;
+                    band_values = values;
+                }
+                elevation
+            },
+            Ok(None) => continue,
+            Err(_e) if index == 0 => {
+                eprintln!("{}", _e);
+                return CoordinateResult::from_error(lat, lon, format!("Internal Server Error {} {}.", lat, lon));
+            }
+            Err(_e) => {eprintln!("{}", _e); continue}
+        };
+        // Blended values are brought into the datum of the preferred dataset first, values of an
+        // unknown datum are only blended with each other.
+        let elevation = match (row_datum, native_datum) {
+            (Some(row_datum), Some(native_datum)) => match appstate.geoids.convert(elevation, lat, lon, row_datum, native_datum) {
+                Ok(converted) => converted,
+                Err(_e) => {eprintln!("{}", _e); continue}
+            },
+            (None, None) => elevation,
+            _ => continue
+        };
+        let weight = match options.blend {
+            Some(buffer) if buffer > 0f64 && index < row.len() - 1 => {
+                let distance: Option<f64> = dataset_row.get("edge_distance");
+                distance.map_or(1f64, |distance| (distance / buffer).clamp(0f64, 1f64))
+            },
+            _ => 1f64
+        };
+        weighted_elevation += remaining_weight * weight * elevation;
+        remaining_weight *= 1f64 - weight;
+        if remaining_weight <= 0f64 {
+            break;
         }
+    }
+    let (preferred, preferred_elevation) = match preferred {
+        Some(preferred) => preferred,
+        None => return CoordinateResult::from_error(lat, lon, format!("No such coordinate {} {}.", lat, lon))
     };
-    let geo = match CoordTransform::new(&spat_point, &spat_data) {
-        Ok(geo) => geo,
-        Err(_e) => {
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("Internal Server Error {} {}.", lat, lon))};
-        }
+    // Without weight left for it the preferred dataset lies on the edge of its coverage and no
+    // other dataset has a value there, so it is used unblended.
+    let elevation = if remaining_weight < 1f64 {
+        weighted_elevation / (1f64 - remaining_weight)
+    } else {
+        preferred_elevation
     };
-    let geotransform = match dataset.geo_transform() {
-        Ok(geotransform) => geotransform,
-        Err(_e) => {
-            eprintln!("{:?}", _e);
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("Internal Server Error {} {}.", lat, lon))};
+    let dataset_layer: Option<String> = preferred.get("layer");
+    let dataset_ground_resolution: Option<f64> = preferred.get("ground_resolution");
+    let dataset_metadata = if options.metadata { Some(DatasetMetadata::from_row(preferred)) } else { None };
+
+    let (elevation, vertical_datum) = match (native_datum, options.datum) {
+        (Some(native_datum), Some(target_datum)) => match appstate.geoids.convert(elevation, lat, lon, native_datum, target_datum) {
+            Ok(converted) => (converted, Some(target_datum)),
+            Err(e) => {
+                eprintln!("{}", e);
+                return CoordinateResult::from_error(lat, lon, format!("Datum conversion to {} not available {} {}.", target_datum.as_str(), lat, lon));
+            }
+        },
+        (None, Some(target_datum)) => {
+            return CoordinateResult::from_error(lat, lon, format!("Unknown vertical datum of dataset, cannot convert to {} {} {}.", target_datum.as_str(), lat, lon));
         }
+        (native_datum, None) => (elevation, native_datum),
     };
-    let (width, height) = dataset.raster_size();
-    let projection_string = dataset.projection();
-    let collection = projection_string.split("EPSG\",").collect::<Vec<&str>>();
-    let epsg_string = match collection.last() {
-        Some(epsg_string) => epsg_string,
+    CoordinateResult {latitude: lat, longitude: lon, elevation: elevation.round() as i32, vertical_datum: vertical_datum.map(|datum| datum.as_str().to_string()), location: None, layer: dataset_layer, ground_resolution: dataset_ground_resolution, layers: None, bands: if band_values.is_empty() { None } else { Some(band_values) }, metadata: dataset_metadata, error: None}
+}
+
+/// Looks up elevation data for a coordinate, serving it from the cache if possible.
+///
+/// # Arguments
+/// * `lat` - Latitude of the point.
+/// * `lon` - Longitude of the point.
+/// * `options` - Options of the lookup.
+/// * `appstate` - Application state containing the database connection and cache.
+///
+/// # Returns
+/// A `CoordinateResult` containing the elevation or an error message.
+async fn cached_lookup_coordinats(lat: f64, lon: f64, options: &LookupOptions, appstate: &AppState) -> CoordinateResult {
+    if options.layers.len() > 1 {
+        return Box::pin(lookup_layers(lat, lon, options, appstate)).await;
+    }
+    let cache_key = options.cache_key(lat, lon);
+    match appstate.cache.get(&cache_key).await {
+        Some(coordinate_result) => coordinate_result,
         None => {
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("Internal Server Error {} {}.", lat, lon))};
-        }
-    };
-    let epsg_number = match (&epsg_string[1..epsg_string.len() - 3]).parse::<i32>() {
-        Ok(epsg_number) => epsg_number,
-        Err(_e) => {
-            eprintln!("{:?}", _e);
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("Internal Server Error {} {}.", lat, lon))};
+            let lookup_result = lookup_coordinats(lat, lon, options, appstate).await;
+            appstate.cache.insert(cache_key, lookup_result.clone()).await;
+            lookup_result
         }
-    };
-    let mut x_coord = [lon];
-    let mut y_coord = [lat];
-    if epsg_number == 25832 {
-        //Also in the back conversion we must make the same exception
-        (x_coord, y_coord) = (y_coord, x_coord);
     }
-    match geo.transform_coords(&mut x_coord, &mut y_coord, &mut [0.0]) {
-        Ok(_) => {},
-        Err(_e) => {
-            eprintln!("{:?}", _e);
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("Internal Server Error {} {}.", lat, lon))};
-        }
+}
+
+/// Looks up the elevation of every requested layer at a coordinate.
+///
+/// # Arguments
+/// * `lat` - Latitude of the point.
+/// * `lon` - Longitude of the point.
+/// * `options` - Options of the lookup with more than one layer.
+/// * `appstate` - Application state containing the database connection and cache.
+///
+/// # Returns
+/// A `CoordinateResult` with the first layer as elevation and all layers in `layers`.
+async fn lookup_layers(lat: f64, lon: f64, options: &LookupOptions, appstate: &AppState) -> CoordinateResult {
+    let mut layer_results: Vec<LayerElevation> = Vec::new();
+    let mut first_result: Option<CoordinateResult> = None;
+    for layer in &options.layers {
+        let layer_options = LookupOptions {layers: vec![layer.clone()], ..options.clone()};
+        let layer_result = cached_lookup_coordinats(lat, lon, &layer_options, appstate).await;
+        layer_results.push(LayerElevation {
+            layer: layer.clone(),
+            elevation: layer_result.elevation,
+            vertical_datum: layer_result.vertical_datum.clone(),
+            bands: layer_result.bands.clone(),
+            metadata: layer_result.metadata.clone(),
+            error: layer_result.error.clone(),
+        });
+        first_result.get_or_insert(layer_result);
+    }
+    let mut coordinate_result = first_result.unwrap_or_else(|| CoordinateResult::from_error(lat, lon, "No layer requested".to_string()));
+    coordinate_result.layers = Some(layer_results);
+    coordinate_result
+}
+
+/// Coordinates converted to EPSG 4326 as latitude and longitude, or the error of each coordinate.
+type TransformedCoordinates = Vec<Result<(f64, f64), String>>;
+
+/// Transforms coordinates from a coordinate system into EPSG 4326.
+///
+/// Coordinates are passed and returned in the order of the API, i.e. latitude (northing)
+/// first and longitude (easting) second.
+///
+/// # Arguments
+/// * `srs` - EPSG code of the input coordinates.
+/// * `coordinates` - The coordinates to transform.
+///
+/// # Returns
+/// * `Ok(TransformedCoordinates)` with the transformed coordinates or a per coordinate error.
+/// * `Err(String)` if the coordinate system is unknown.
+fn transform_to_wgs84(srs: u32, coordinates: &[(f64, f64)]) -> Result<TransformedCoordinates, String> {
+    let spat_input = raster::epsg_spatial_ref(srs)?;
+    let spat_point = raster::epsg_spatial_ref(4326)?;
+    let geo = match CoordTransform::new(&spat_input, &spat_point) {
+        Ok(geo) => geo,
+        Err(e) => return Err(format!("CoordTransform could not be created: {}", e))
     };
-    let resolution_x: f64 = width as f64 / ((500f64 + geotransform[0]) - (500f64 + geotransform[0] + width as f64 * geotransform[1] + height as f64 * geotransform[2])).abs();
-    let resolution_y: f64 = height as f64 / ((500f64 + geotransform[3] + width as f64 * geotransform[4] + height as f64 * geotransform[5]) - (500f64 + geotransform[3])).abs();
-    let pixel_x = ((x_coord[0]-geotransform[0]).round() * resolution_x).round();
-    let pixel_y = ((y_coord[0]-(geotransform[3] + width as f64 * geotransform[4] + height as f64 * geotransform[5])).round() * resolution_y).round();
-    let rasterband: RasterBand = match dataset.rasterband(1) {
-        Ok(rasterband) => rasterband,
-        Err(_e) => {
-            eprintln!("{:?}", _e);
-            return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("Internal Server Error {} {}.", lat, lon))};
+    Ok(coordinates.iter().map(|(northing, easting)| {
+        let mut x_coord = [*easting];
+        let mut y_coord = [*northing];
+        match geo.transform_coords(&mut x_coord, &mut y_coord, &mut [0.0]) {
+            Ok(_) => Ok((y_coord[0], x_coord[0])),
+            Err(_) => Err(format!("Coordinate {} {} could not be transformed from EPSG:{}.", northing, easting, srs))
         }
-    };
-    if let Ok(rv) = rasterband.read_as::<u8>((pixel_x as isize, pixel_y as isize), (1, 1), (1, 1), None) {
-        return CoordinateResult {latitude: lat, longitude: lon, elevation: rv.data()[0] as i32, error: None };
+    }).collect())
+}
+
+/// Resolves the locations of a request into coordinates in EPSG 4326.
+///
+/// # Arguments
+/// * `locations` - The locations of the request.
+/// * `srs` - EPSG code of plain coordinates, EPSG 4326 if not set.
+///
+/// # Returns
+/// Latitude and longitude of every location or an error message.
+fn resolve_locations(locations: &[Location], srs: Option<u32>) -> Vec<Result<(f64, f64), String>> {
+    let coordinates: Vec<(f64, f64)> = locations.iter().filter_map(|location| match location {
+        Location::Coordinate(lat, lon) => Some((*lat, *lon)),
+        _ => None
+    }).collect();
+    let mut transformed = match srs {
+        Some(srs) => match transform_to_wgs84(srs, &coordinates) {
+            Ok(transformed) => transformed,
+            Err(e) => coordinates.iter().map(|_| Err(e.clone())).collect()
+        },
+        None => coordinates.into_iter().map(Ok).collect()
+    }.into_iter();
+    locations.iter().map(|location| match location {
+        Location::Coordinate(_, _) => transformed.next().unwrap_or(Err("Internal Server Error".to_string())),
+        Location::Encoded(code) => encodings::decode_location(code),
+        Location::Invalid(error) => Err(error.clone()),
+    }).collect()
+}
+
+/// Parses a location given as `latitude,longitude` or as location identifier.
+fn parse_location(location: &str) -> Location {
+    let latlon = location.split(",").collect::<Vec<&str>>();
+    if latlon.len() != 2 {
+        return Location::Encoded(location.to_string());
+    }
+    match (latlon[0].parse::<f64>(), latlon[1].parse::<f64>()) {
+        (Ok(lat), Ok(lon)) => Location::Coordinate(lat, lon),
+        _ => Location::Invalid(format!("Bad parameter format {}.", location))
     }
-    else {
-        return CoordinateResult {latitude: lat, longitude: lon, elevation: 0i32, error: Option::from(format!("Internal Server Error {} {}.", lat, lon))};
+}
+
+/// Looks up a list of locations given as coordinates in an optional input coordinate system
+/// or as location identifiers.
+///
+/// Results of coordinates carry them as they were passed in, so they are echoed back in the
+/// input coordinate system. Results of location identifiers carry the decoded coordinate.
+///
+/// # Arguments
+/// * `locations` - The locations to look up.
+/// * `srs` - EPSG code of plain coordinates, EPSG 4326 if not set.
+/// * `options` - Options of the lookup.
+/// * `appstate` - Application state containing the database connection and cache.
+///
+/// # Returns
+/// One `CoordinateResult` per location.
+async fn lookup_location_list(locations: Vec<Location>, srs: Option<u32>, options: &LookupOptions, appstate: &AppState) -> Vec<CoordinateResult> {
+    let resolved = resolve_locations(&locations, srs);
+    let mut result_list: Vec<CoordinateResult> = Vec::new();
+    for (location, coordinate) in locations.into_iter().zip(resolved) {
+        let mut coordinate_result = match coordinate {
+            Ok((lat, lon)) => cached_lookup_coordinats(lat, lon, options, appstate).await,
+            Err(e) => CoordinateResult::from_error(0f64, 0f64, e)
+        };
+        match location {
+            Location::Coordinate(input_lat, input_lon) => {
+                coordinate_result.latitude = input_lat;
+                coordinate_result.longitude = input_lon;
+            }
+            Location::Encoded(code) => coordinate_result.location = Some(code),
+            Location::Invalid(_) => {}
+        };
+        result_list.push(coordinate_result);
     }
+    result_list
 }
 
 /// Handles POST requests to lookup coordinates.
@@ -296,24 +865,15 @@ async fn lookup_coordinats(lat: f64, lon: f64, pool: &ConnectionPool, config_dat
 #[debug_handler]
 async fn post_lookup_coordinates(
     State(appstate): State<AppState>, Json(payload): Json<PostCoordinates>) -> (StatusCode, Json<CoordinateResultList>) {
-    let pool = appstate.db_connection;
-    let config_datadir = appstate.datadir;
-    let cache = appstate.cache;
-    let mut result_list: Vec<CoordinateResult> = Vec::new();
-    let locations = payload.locations;
-    for location in locations {
-        let lat = location.latitude;
-        let lon = location.longitude;
-        let coordinate_result = match cache.get(&format!("{},{}", lat, lon)).await {
-            Some(coordinate_result) => coordinate_result,
-            None => {
-                let lookup_result = lookup_coordinats(lat, lon, &pool, &config_datadir).await;
-                cache.insert(format!("{},{}", lat, lon), lookup_result.clone()).await;
-                lookup_result
-            }
-        };
-        result_list.push(coordinate_result);
-    }
+    let mut layers = payload.layers.unwrap_or_default();
+    layers.extend(payload.layer);
+    let options = LookupOptions {datum: payload.datum, layers, blend: payload.blend, resolution: payload.resolution, metadata: payload.metadata.unwrap_or(false)};
+    let locations = payload.locations.into_iter().map(|location| match (location.latitude, location.longitude, location.location) {
+        (Some(lat), Some(lon), None) => Location::Coordinate(lat, lon),
+        (None, None, Some(code)) => Location::Encoded(code),
+        _ => Location::Invalid("Either latitude and longitude or location is required.".to_string())
+    }).collect();
+    let result_list = lookup_location_list(locations, payload.srs, &options, &appstate).await;
     (StatusCode::OK, Json(CoordinateResultList {results: result_list}))
 }
 
@@ -329,130 +889,390 @@ async fn post_lookup_coordinates(
 async fn get_lookup_coordinates(
     State(appstate): State<AppState>, axum::extract::Query(params):
     axum::extract::Query<HashMap<String, String>>) -> (StatusCode, Json<CoordinateResultList>) {
-    let pool = appstate.db_connection;
-    let config_datadir = appstate.datadir;
-    let cache = appstate.cache;
-
     let mut result_list: Vec<CoordinateResult> = Vec::new();
-    let location_string = match params.get("locations"){
-        Some(locations) => locations,
-        None => {
-            result_list.push(CoordinateResult {latitude: 0f64, longitude: 0f64, elevation: 0i32, error: Option::from("locations is a required parameter".to_string())});
-            return (StatusCode::OK, Json(CoordinateResultList {results: result_list}));
-        }
-    };
-    let locations = location_string.split("|").collect::<Vec<&str>>();
-    for location in locations {
-        let latlon = location.split(",").collect::<Vec<&str>>();
-        let lat_string = match latlon.first() {
-            Some(lat_string) => lat_string,
+    let datum = match params.get("datum") {
+        Some(datum_string) => match VerticalDatum::from_name(datum_string) {
+            Some(datum) => Some(datum),
             None => {
-                result_list.push(CoordinateResult {latitude: 0f64, longitude: 0f64, elevation: 0i32, error: Option::from(format!("Bad parameter format {}.", location))});
-                continue;
+                result_list.push(CoordinateResult::from_error(0f64, 0f64, format!("Unknown datum {}.", datum_string)));
+                return (StatusCode::OK, Json(CoordinateResultList {results: result_list}));
             }
-        };
-        let lon_string = match latlon.last() {
-            Some(lon_string) => lon_string,
-            None => {
-                result_list.push(CoordinateResult {latitude: 0f64, longitude: 0f64, elevation: 0i32, error: Option::from(format!("Bad parameter format {}.", location))});
-                continue;
+        },
+        None => None
+    };
+    // `layers` and `layer` are merged like in POST requests.
+    let layers: Vec<String> = [params.get("layers"), params.get("layer")].into_iter().flatten()
+        .flat_map(|layer_string| layer_string.split(","))
+        .filter(|layer| !layer.is_empty())
+        .map(|layer| layer.to_string())
+        .collect();
+    let blend = match params.get("blend") {
+        Some(blend_string) => match blend_string.parse::<f64>() {
+            Ok(blend) => Some(blend),
+            Err(_) => {
+                result_list.push(CoordinateResult::from_error(0f64, 0f64, format!("Bad parameter format {}.", blend_string)));
+                return (StatusCode::OK, Json(CoordinateResultList {results: result_list}));
             }
-        };
-        let lon = match lon_string.parse::<f64>() {
-            Ok(lon) => lon,
+        },
+        None => None
+    };
+    let resolution = match params.get("resolution") {
+        Some(resolution_string) => match resolution_string.parse::<f64>() {
+            Ok(resolution) => Some(resolution),
             Err(_) => {
-                result_list.push(CoordinateResult {latitude: 0f64, longitude: 0f64, elevation: 0i32, error: Option::from(format!("Bad parameter format {}.", location))});
-                continue;
+                result_list.push(CoordinateResult::from_error(0f64, 0f64, format!("Bad parameter format {}.", resolution_string)));
+                return (StatusCode::OK, Json(CoordinateResultList {results: result_list}));
             }
-        };
-        let lat = match lat_string.parse::<f64>() {
-            Ok(lat) => lat,
+        },
+        None => None
+    };
+    let metadata = match params.get("metadata") {
+        Some(metadata_string) => match metadata_string.parse::<bool>() {
+            Ok(metadata) => metadata,
             Err(_) => {
-                result_list.push(CoordinateResult {latitude: 0f64, longitude: 0f64, elevation: 0i32, error: Option::from(format!("Bad parameter format {}.", location))});
-                continue;
+                result_list.push(CoordinateResult::from_error(0f64, 0f64, format!("Bad parameter format {}.", metadata_string)));
+                return (StatusCode::OK, Json(CoordinateResultList {results: result_list}));
             }
-        };
-        let coordinate_result = match cache.get(&format!("{},{}", lat, lon)).await {
-            Some(coordinate_result) => coordinate_result,
-            None => {
-                let lookup_result = lookup_coordinats(lat, lon, &pool, &config_datadir).await;
-                cache.insert(format!("{},{}", lat, lon), lookup_result.clone()).await;
-                lookup_result
+        },
+        None => false
+    };
+    let options = LookupOptions {datum, layers, blend, resolution, metadata};
+    let srs = match params.get("srs").or(params.get("crs")) {
+        Some(srs_string) => match srs_string.trim_start_matches("EPSG:").parse::<u32>() {
+            Ok(srs) => Some(srs),
+            Err(_) => {
+                result_list.push(CoordinateResult::from_error(0f64, 0f64, format!("Bad parameter format {}.", srs_string)));
+                return (StatusCode::OK, Json(CoordinateResultList {results: result_list}));
             }
-        };
-        result_list.push(coordinate_result);
-    }
+        },
+        None => None
+    };
+    let location_string = match params.get("locations"){
+        Some(locations) => locations,
+        None => {
+            result_list.push(CoordinateResult::from_error(0f64, 0f64, "locations is a required parameter".to_string()));
+            return (StatusCode::OK, Json(CoordinateResultList {results: result_list}));
+        }
+    };
+    let locations = location_string.split("|").map(parse_location).collect();
+    result_list.append(&mut lookup_location_list(locations, srs, &options, &appstate).await);
     (StatusCode::OK, Json(CoordinateResultList {results: result_list}))
 }
 
-/// Main function to start the server and handle incoming requests.
-#[tokio::main]
-async fn main() {
-    //Bekomme Lat Long übergeben
-    //Frage PostGIS Server welches Polygon den Punkt enthält
-    //PostGIS Datensatz: ID, Pfad unterhalb Dataroot, Auflösung, Projektion, Polygon(Boundary)
-    //Wähle Layer mit höchster Auflösung
-    //Öffne GeoTIFF und lese Höhe aus
-    //Bei gesetzter regenerate Flag wird Datenbank gelöscht und neu geschrieben
-    let args = Cli::parse();
-    let dbuser = match env::var("DBUSER") {
-        Ok(dbuser) => dbuser,
-        Err(_) => {println!("$DBUSER is not set"); std::process::exit(exitcode::CONFIG)}
-    };
-    let dbpass = match env::var("DBPASS") {
-        Ok(dbpass) => dbpass,
-        Err(_) => {println!("$DBPASS is not set"); std::process::exit(exitcode::CONFIG)}
-    };
-    let dbhost = match env::var("DBHOST") {
-        Ok(dbhost) => dbhost,
-        Err(_) => {println!("$DBHOST is not set"); std::process::exit(exitcode::CONFIG)}
-    };
-    let dbdatabase = match env::var("DBDATABASE") {
-        Ok(dbdatabase) => dbdatabase,
-        Err(_) => {println!("$DBDATABSE is not set"); std::process::exit(exitcode::CONFIG)}
-    };
-    let config_datadir = match env::var("DATADIR") {
-        Ok(config_datadir) => config_datadir,
-        Err(_) => {println!("$DATADIR is not set"); std::process::exit(exitcode::CONFIG)}
-    };
-    let api_url = match env::var("APIURL") {
-        Ok(api_url) => api_url,
-        Err(_) => {println!("APIURL is not set"); std::process::exit(exitcode::CONFIG)}
+/// Writes a report as JSON, exiting if it cannot be written.
+///
+/// # Arguments
+/// * `report_path` - Path of the report file.
+/// * `report` - The report to write.
+fn write_report<T: Serialize>(report_path: &str, report: &T) {
+    let report_json = match serde_json::to_string_pretty(report) {
+        Ok(report_json) => report_json,
+        Err(e) => {println!("Report could not be serialized: {}", e); std::process::exit(exitcode::SOFTWARE)}
     };
-    let cache_size = match env::var("CACHESIZE") {
-        Ok(cache_size) => cache_size,
-        Err(_) => {println!("CACHESIZE is not set"); std::process::exit(exitcode::CONFIG)}
+    match std::fs::write(report_path, report_json) {
+        Ok(_) => {},
+        Err(e) => {println!("Report could not be written to {}: {}", report_path, e); std::process::exit(exitcode::CANTCREAT)}
     };
+}
+
+/// Returns a required configuration value, exiting if it is not set.
+///
+/// # Arguments
+/// * `value` - The configured value.
+/// * `name` - Name of the environment variable the value is read from.
+fn required(value: &Option<String>, name: &str) -> String {
+    match value {
+        Some(value) => value.clone(),
+        None => {println!("${} is not set", name); std::process::exit(exitcode::CONFIG)}
+    }
+}
+
+/// Creates the connection pool to the PostgreSQL database, exiting if it is not configured.
+async fn connect(config: &ConfigArgs) -> ConnectionPool {
+    let dbuser = required(&config.db_user, "DBUSER");
+    let dbpass = required(&config.db_pass, "DBPASS");
+    let dbhost = required(&config.db_host, "DBHOST");
+    let dbdatabase = required(&config.db_database, "DBDATABASE");
     let db_config_string = format!("postgres://{}?dbname={}&user={}&password={}", dbhost, dbdatabase, dbuser, dbpass);
     let manager = match
         PostgresConnectionManager::new_from_stringlike(db_config_string, NoTls) {
         Ok(manager) => manager,
         Err(e) => {println!("DB Connection not sucessfull: {}", e); std::process::exit(exitcode::UNAVAILABLE)}
     };
-    let pool = Pool::builder().build(manager).await.unwrap();
-    if args.regenerate == true {
-        match parse_data_create_database(config_datadir, pool.clone()).await {
-            Ok(_) => {std::process::exit(exitcode::OK);}
-            Err(e) => {println!("Database Regeneration unsucessfull: {}", e); std::process::exit(exitcode::SOFTWARE);}
-        };
+    Pool::builder().build(manager).await.unwrap()
+}
 
+/// Connects to the PostgreSQL database for commands that can do without it.
+///
+/// # Arguments
+/// * `config` - Configuration shared by all subcommands.
+/// * `timeout` - Time to wait for a connection.
+///
+/// # Returns
+/// The connection pool, or `None` if the database is not configured. Connections are only made on
+/// first use, which waits at most `timeout` for an unreachable database.
+async fn try_connect(config: &ConfigArgs, timeout: Duration) -> Option<ConnectionPool> {
+    let (dbuser, dbpass, dbhost, dbdatabase) = match (&config.db_user, &config.db_pass, &config.db_host, &config.db_database) {
+        (Some(dbuser), Some(dbpass), Some(dbhost), Some(dbdatabase)) => (dbuser, dbpass, dbhost, dbdatabase),
+        _ => return None
+    };
+    let db_config_string = format!("postgres://{}?dbname={}&user={}&password={}", dbhost, dbdatabase, dbuser, dbpass);
+    let manager = match PostgresConnectionManager::new_from_stringlike(db_config_string, NoTls) {
+        Ok(manager) => manager,
+        Err(e) => {println!("DB Connection not sucessfull: {}", e); return None}
+    };
+    match Pool::builder().connection_timeout(timeout).build(manager).await {
+        Ok(pool) => Some(pool),
+        Err(e) => {println!("DB Connection not sucessfull: {}", e); None}
+    }
+}
+
+/// Reads the ingestion configuration from the environment and the index options, exiting if it is invalid.
+fn ingest_config(index_args: &IndexArgs) -> IngestConfig {
+    let mut ingest_config = match IngestConfig::from_env() {
+        Ok(ingest_config) => ingest_config,
+        Err(e) => {println!("{}", e); std::process::exit(exitcode::CONFIG)}
+    };
+    ingest_config.full = index_args.full;
+    ingest_config.strict = index_args.strict;
+    ingest_config.build_overviews = index_args.build_overviews;
+    if let Some(workers) = index_args.workers {
+        ingest_config.workers = workers;
     }
-    let cache_size_u64 = match cache_size.parse::<u64>(){
-        Ok(cache_size_u64) => cache_size_u64,
-        Err(_) => {println!("Invalid value for CACHESIZE"); std::process::exit(exitcode::CONFIG)}
+    ingest_config
+}
+
+/// Synchronises geo_data with the data directory and writes the requested report.
+///
+/// # Arguments
+/// * `datadir` - Directory containing geospatial data.
+/// * `pool` - The connection pool to the PostgreSQL database.
+/// * `ingest_config` - Configuration of the dataset ingestion.
+/// * `index_args` - Options of the sync.
+///
+/// # Returns
+/// The outcome of every file, exits if the index could not be updated at all.
+async fn index(datadir: &str, pool: &ConnectionPool, ingest_config: &IngestConfig, index_args: &IndexArgs) -> ingest::IngestReport {
+    let report = match parse_data_create_database(datadir.to_string(), pool.clone(), ingest_config).await {
+        Ok(report) => report,
+        Err(e) => {println!("Database Regeneration unsucessfull: {}", e); std::process::exit(exitcode::SOFTWARE);}
     };
-    let cache:Cache<String, CoordinateResult> = Cache::new(cache_size_u64);
+    if let Some(report_path) = &index_args.report {
+        write_report(report_path, &report);
+    }
+    report
+}
+
+/// Creates the state shared by the lookups, exiting if the band configuration is invalid.
+///
+/// # Arguments
+/// * `pool` - The connection pool to the PostgreSQL database.
+/// * `datadir` - Directory containing geospatial data.
+/// * `cache_size` - Number of lookups kept in the cache.
+/// * `ingest_config` - Configuration of the dataset ingestion.
+fn app_state(pool: ConnectionPool, datadir: String, cache_size: u64, ingest_config: IngestConfig) -> AppState {
+    let layer_bands = match layer_bands_from_env() {
+        Ok(layer_bands) => layer_bands,
+        Err(e) => {println!("{}", e); std::process::exit(exitcode::CONFIG)}
+    };
+    let geoids = match GeoidModels::from_env() {
+        Ok(geoids) => geoids,
+        Err(e) => {println!("{}", e); std::process::exit(exitcode::CONFIG)}
+    };
+    let cache:Cache<String, CoordinateResult> = Cache::builder()
+        .max_capacity(cache_size)
+        .support_invalidation_closures()
+        .build();
+    AppState{db_connection: pool, datadir, cache, geoids, layer_bands: Arc::new(layer_bands),
+        ingest_config: Arc::new(ingest_config), admin_token: env::var("ADMINTOKEN").ok().filter(|admin_token| !admin_token.is_empty())}
+}
+
+/// Serves the API, optionally after synchronising the index.
+///
+/// # Arguments
+/// * `config` - Configuration shared by all subcommands.
+/// * `serve_args` - Options of the server.
+async fn serve(config: &ConfigArgs, serve_args: ServeArgs) {
+    let config_datadir = required(&config.datadir, "DATADIR");
+    let api_url = required(&config.api_url, "APIURL");
+    let cache_size = match serve_args.cache_size {
+        Some(cache_size) => cache_size,
+        None => {println!("CACHESIZE is not set"); std::process::exit(exitcode::CONFIG)}
+    };
+    let ingest_config = ingest_config(&serve_args.index_args);
+    let pool = connect(config).await;
+    if serve_args.index {
+        // Files that failed to be indexed are reported, the server starts with the rest of the index.
+        index(&config_datadir, &pool, &ingest_config, &serve_args.index_args).await;
+    }
+    let appstate = app_state(pool, config_datadir, cache_size, ingest_config);
+    let _watcher = if serve_args.watch {
+        match watch::watch_datadir(appstate.clone()) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {println!("{}", e); std::process::exit(exitcode::SOFTWARE)}
+        }
+    } else {
+        None
+    };
+    let admin_routes = Router::new()
+        .route("/admin/datasets", get(catalog::get_datasets).post(admin::post_dataset).layer(DefaultBodyLimit::disable()))
+        .route("/admin/datasets/:id", get(catalog::get_dataset).delete(admin::delete_dataset))
+        .route_layer(middleware::from_fn_with_state(appstate.clone(), admin::require_admin));
     let app = Router::new()
         // `POST /users` goes to `create_user`
-        .route(&*api_url, post(post_lookup_coordinates)).route(&*api_url, get(get_lookup_coordinates)).with_state(AppState{db_connection: pool, datadir: config_datadir, cache: cache});
-    let listener = match tokio::net::TcpListener::bind("0.0.0.0:3000").await {
+        .route(&api_url, post(post_lookup_coordinates)).route(&api_url, get(get_lookup_coordinates))
+        .route("/line-of-sight", post(line_of_sight::post_line_of_sight))
+        .route("/viewshed", post(viewshed::post_viewshed))
+        .route("/zonal-statistics", post(zonal_statistics::post_zonal_statistics))
+        .route("/export", post(export::post_export))
+        .route("/coverage", get(coverage::get_coverage))
+        .merge(admin_routes)
+        .with_state(appstate);
+    let listener = match tokio::net::TcpListener::bind(&serve_args.listen).await {
         Ok(listener) => listener,
         Err(e) => {println!("Setting up TCP Listener unsucessfull: {}", e); std::process::exit(exitcode::SOFTWARE)}
     };
-    let _ = match axum::serve(listener, app).await {
+    match axum::serve(listener, app).await {
         Ok(_) => {},
         Err(e) => {println!("Starting Server unsucessfull: {}", e); std::process::exit(exitcode::SOFTWARE)}
     };
+}
+
+/// Returns the options of `serve` when no subcommand is given, taken from the environment and the defaults.
+fn default_serve_args() -> ServeArgs {
+    let matches = ServeArgs::augment_args(clap::Command::new("serve")).get_matches_from(["serve"]);
+    match ServeArgs::from_arg_matches(&matches) {
+        Ok(serve_args) => serve_args,
+        Err(e) => e.exit()
+    }
+}
+
+/// Main function dispatching the subcommands, serving the API if none is given.
+#[tokio::main]
+async fn main() {
+    //Bekomme Lat Long übergeben
+    //Frage PostGIS Server welches Polygon den Punkt enthält
+    //PostGIS Datensatz: ID, Pfad unterhalb Dataroot, Auflösung, Projektion, Polygon(Boundary)
+    //Wähle Layer mit höchster Auflösung
+    //Öffne GeoTIFF und lese Höhe aus
+    //Mit dem index Befehl wird die Datenbank mit dem Datenverzeichnis abgeglichen
+    let args = Cli::parse();
+    let config = args.config;
+    let command = match args.command {
+        Some(command) => command,
+        None => Command::Serve(default_serve_args())
+    };
+    match raster::configure_remote_access() {
+        Ok(_) => {},
+        Err(e) => {println!("{}", e); std::process::exit(exitcode::CONFIG)}
+    };
+    match command {
+        Command::Serve(serve_args) => serve(&config, serve_args).await,
+        Command::Index(index_args) => {
+            let config_datadir = required(&config.datadir, "DATADIR");
+            let ingest_config = ingest_config(&index_args);
+            let pool = connect(&config).await;
+            let report = index(&config_datadir, &pool, &ingest_config, &index_args).await;
+            // Failed files are a partial failure even if the rest of the index was updated.
+            if report.failed > 0 {
+                std::process::exit(exitcode::DATAERR);
+            }
+        },
+        Command::Validate {path, report: report_path} => {
+            // Validation works without the database, only the overlaps with indexed datasets are skipped.
+            let pool = try_connect(&config, VALIDATE_DB_TIMEOUT).await;
+            let report = match validate::validate_path(&path, pool.as_ref()).await {
+                Ok(report) => report,
+                Err(e) => {println!("Validation unsucessfull: {}", e); std::process::exit(exitcode::NOINPUT)}
+            };
+            report.print();
+            if let Some(report_path) = &report_path {
+                write_report(report_path, &report);
+            }
+            if report.errors > 0 {
+                std::process::exit(exitcode::DATAERR);
+            }
+        },
+        Command::Lookup {locations, lookup} => {
+            let config_datadir = required(&config.datadir, "DATADIR");
+            let pool = connect(&config).await;
+            let ingest_config = match IngestConfig::from_env() {
+                Ok(ingest_config) => ingest_config,
+                Err(e) => {println!("{}", e); std::process::exit(exitcode::CONFIG)}
+            };
+            let appstate = app_state(pool, config_datadir, locations.len() as u64, ingest_config);
+            let locations = locations.iter().map(|location| parse_location(location)).collect();
+            let results = lookup_location_list(locations, lookup.srs, &lookup.options(), &appstate).await;
+            let failed = results.iter().any(|result| result.error.is_some());
+            match serde_json::to_string_pretty(&CoordinateResultList {results}) {
+                Ok(results_json) => println!("{}", results_json),
+                Err(e) => {println!("Results could not be serialized: {}", e); std::process::exit(exitcode::SOFTWARE)}
+            };
+            if failed {
+                std::process::exit(exitcode::DATAERR);
+            }
+        },
+        Command::Coverage {layer, group_by, classes, zoom} => {
+            let pool = connect(&config).await;
+            let mut options = coverage::CoverageOptions {layer, ..coverage::CoverageOptions::default()};
+            if let Some(group_by) = &group_by {
+                if let Err(e) = options.set_groups(group_by) {
+                    println!("{}", e);
+                    std::process::exit(exitcode::USAGE);
+                }
+            }
+            if !classes.is_empty() {
+                if let Err(e) = options.set_classes(classes) {
+                    println!("{}", e);
+                    std::process::exit(exitcode::USAGE);
+                }
+            }
+            if let Some(zoom) = zoom {
+                options.tolerance = coverage::zoom_tolerance(zoom);
+            }
+            match coverage::query_coverage(&pool, &options).await {
+                Ok(coverage) => println!("{}", coverage),
+                Err(e) => {println!("{}", e); std::process::exit(exitcode::SOFTWARE)}
+            };
+        },
+        Command::CacheWarm {file, server, batch_size, lookup} => {
+            let api_url = required(&config.api_url, "APIURL");
+            let url = format!("{}{}", server.trim_end_matches('/'), api_url);
+            let report = match cache_warm::warm_cache(&file, &url, &lookup.options(), lookup.srs, batch_size).await {
+                Ok(report) => report,
+                Err(e) => {println!("Cache warming unsucessfull: {}", e); std::process::exit(exitcode::UNAVAILABLE)}
+            };
+            println!("{} locations looked up, {} failed", report.requested, report.failed);
+            if report.failed > 0 {
+                std::process::exit(exitcode::DATAERR);
+            }
+        }
+    };
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gdal::DriverManager;
+    use gdal::raster::Buffer;
+    use gdal::spatial_ref::SpatialRef;
 
+    #[test]
+    fn reads_pixel_of_geographic_raster() {
+        let path = env::temp_dir().join(format!("faster-elevation-pixel-{}.tif", std::process::id()));
+        {
+            let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+            let mut dataset = driver.create_with_band_type::<f64, _>(&path, 4, 3, 1).unwrap();
+            dataset.set_geo_transform(&[10.0, 0.5, 0.0, 50.0, 0.0, -0.5]).unwrap();
+            dataset.set_spatial_ref(&SpatialRef::from_epsg(4326).unwrap()).unwrap();
+            let values: Vec<f64> = (0..12).map(|value| value as f64).collect();
+            dataset.rasterband(1).unwrap().write((0, 0), (4, 3), &mut Buffer::new((4, 3), values)).unwrap();
+        }
+        // 49.2, 11.2 lies in column 2 and row 1 counted from the northern edge.
+        let inside = read_dataset_elevation(&path, 49.2, 11.2, (None, None), None);
+        let outside = read_dataset_elevation(&path, 49.2, 12.2, (None, None), None);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(inside.unwrap().map(|(elevation, _)| elevation), Some(6.0));
+        assert!(outside.unwrap().is_none());
+    }
 }
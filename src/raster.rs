@@ -0,0 +1,506 @@
+use gdal::{Dataset, DriverManager, GeoTransform, GeoTransformEx};
+use gdal::raster::{Buffer, GdalType, RasterCreationOptions};
+use gdal::spatial_ref::{AxisMappingStrategy, CoordTransform, SpatialRef};
+use geo::{BoundingRect, Geometry, Polygon, Rect};
+use geozero::wkb;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{elevation_band, AppState};
+use crate::datum::{GeoidModels, VerticalDatum};
+
+/// Metres per degree of latitude.
+pub const METRES_PER_DEGREE: f64 = 111_320.0;
+/// Order of geo_data rows from the preferred dataset to the least preferred one:
+/// explicit source priority first, then ground resolution, then the most recent acquisition.
+pub const DATASET_ORDER: &str = "priority DESC NULLS LAST, ground_resolution ASC NULLS LAST, acquisition_date DESC NULLS LAST";
+/// Largest raster window (per axis) read at full resolution while filling a grid.
+const MAX_WINDOW_SIZE: usize = 4096;
+
+/// GDAL virtual file system prefixes of datasets inside archives. Their paths are stored as the
+/// prefix followed by the path relative to the data directory.
+pub const VSI_ARCHIVE_PREFIXES: [&str; 3] = ["/vsizip/", "/vsitar/", "/vsigzip/"];
+/// GDAL virtual file system prefixes of remote datasets, stored with their full URI.
+pub const VSI_REMOTE_PREFIXES: [&str; 5] = ["/vsicurl/", "/vsis3/", "/vsigs/", "/vsiaz/", "/vsiadls/"];
+/// Size in bytes of the process wide cache of regions downloaded from remote datasets.
+const REMOTE_REGION_CACHE_SIZE: &str = "134217728";
+
+/// Counter used to give every in-memory output raster a unique name.
+static MEM_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Regular grid of elevations in EPSG 4326, stored row by row starting in the north west corner.
+pub struct ElevationGrid {
+    /// Longitude of the western edge of the grid.
+    pub origin_lon: f64,
+    /// Latitude of the northern edge of the grid.
+    pub origin_lat: f64,
+    /// Width of a cell in degrees.
+    pub pixel_lon: f64,
+    /// Height of a cell in degrees.
+    pub pixel_lat: f64,
+    pub width: usize,
+    pub height: usize,
+    /// Elevation of every cell, `None` where no dataset provides a value.
+    pub values: Vec<Option<f64>>,
+    /// Vertical datum of the elevations, `None` if unknown.
+    pub vertical_datum: Option<VerticalDatum>,
+}
+
+impl ElevationGrid {
+    /// Creates an empty grid with `width` x `height` cells covering a bounding box in EPSG 4326.
+    pub fn new(bbox: Rect<f64>, width: usize, height: usize) -> ElevationGrid {
+        ElevationGrid {
+            origin_lon: bbox.min().x,
+            origin_lat: bbox.max().y,
+            pixel_lon: bbox.width() / width as f64,
+            pixel_lat: bbox.height() / height as f64,
+            width,
+            height,
+            values: vec![None; width * height],
+            vertical_datum: None,
+        }
+    }
+
+    /// Returns the longitude and latitude of the centre of a cell.
+    pub fn cell_center(&self, col: usize, row: usize) -> (f64, f64) {
+        (self.origin_lon + (col as f64 + 0.5) * self.pixel_lon, self.origin_lat - (row as f64 + 0.5) * self.pixel_lat)
+    }
+
+    /// Returns the elevation of a cell.
+    pub fn get(&self, col: usize, row: usize) -> Option<f64> {
+        self.values[row * self.width + col]
+    }
+
+    /// Returns the GDAL geotransform of the grid.
+    pub fn geo_transform(&self) -> GeoTransform {
+        [self.origin_lon, self.pixel_lon, 0f64, self.origin_lat, 0f64, -self.pixel_lat]
+    }
+}
+
+/// Returns whether a pixel value is NoData, either the NoData value of its band or NaN.
+pub fn is_no_data(value: f64, no_data: Option<f64>) -> bool {
+    value.is_nan() || no_data == Some(value)
+}
+
+/// Dataset an area is read from.
+pub struct SourceDataset {
+    /// Full path of the dataset.
+    pub path: PathBuf,
+    /// Index of the band holding the elevation, starting at 1.
+    pub band_index: usize,
+    /// Vertical datum of the elevations, `None` if unknown.
+    pub vertical_datum: Option<VerticalDatum>,
+}
+
+/// Queries all datasets of a layer intersecting an area, preferred dataset first.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection and band configuration.
+/// * `area` - Geometry in EPSG 4326.
+/// * `layer` - Layer of the datasets.
+///
+/// # Returns
+/// * `Ok(Vec<SourceDataset>)` with the datasets to read.
+/// * `Err(String)` if the database could not be queried.
+pub async fn source_datasets(appstate: &AppState, area: &Geometry<f64>, layer: &str) -> Result<Vec<SourceDataset>, String> {
+    let conn = match appstate.db_connection.get().await {
+        Ok(conn) => conn,
+        Err(e) => {eprintln!("{:?}", e); return Err("Internal Server Error".to_string())}
+    };
+    let rows = match conn.query(
+        &format!("SELECT path, layer, vertical_datum FROM geo_data WHERE ST_Intersects(object, ST_SetSRID(CAST ($1 AS geometry),4326)) AND layer = $2 ORDER BY {};", DATASET_ORDER),
+        &[&wkb::Encode(area.clone()), &layer],
+    ).await {
+        Ok(rows) => rows,
+        Err(e) => {eprintln!("{:?}", e); return Err("Internal Server Error".to_string())}
+    };
+    let datadir = PathBuf::from(&appstate.datadir);
+    let datasets: Vec<SourceDataset> = rows.iter().map(|row| {
+        let path: String = row.get("path");
+        let layer: Option<String> = row.get("layer");
+        let vertical_datum: Option<String> = row.get("vertical_datum");
+        SourceDataset {
+            path: dataset_path(&datadir, &path),
+            band_index: elevation_band(&appstate.layer_bands, layer.as_deref()),
+            vertical_datum: vertical_datum.as_deref().and_then(VerticalDatum::from_name),
+        }
+    }).collect();
+    // A layer VRT mosaics all datasets of the layer, which share one vertical datum, so it is read instead.
+    match appstate.ingest_config.layer_vrt(layer) {
+        Some(layer_vrt) if !datasets.is_empty() => Ok(vec![SourceDataset {
+            path: layer_vrt,
+            band_index: elevation_band(&appstate.layer_bands, Some(layer)),
+            vertical_datum: datasets[0].vertical_datum,
+        }]),
+        _ => Ok(datasets)
+    }
+}
+
+/// Returns the datum the elevations of a grid are returned in, the requested one or else that of the
+/// preferred dataset.
+///
+/// # Arguments
+/// * `geoids` - Geoid grids available for conversions.
+/// * `datasets` - Datasets the grid is read from, preferred dataset first.
+/// * `requested` - Datum requested by the client.
+///
+/// # Returns
+/// * `Ok(Option<VerticalDatum>)` with the datum of the grid, `None` if it is unknown.
+/// * `Err(String)` if a dataset cannot be converted into it.
+pub fn target_datum(geoids: &GeoidModels, datasets: &[SourceDataset], requested: Option<VerticalDatum>) -> Result<Option<VerticalDatum>, String> {
+    let target = match requested.or_else(|| datasets.first().and_then(|dataset| dataset.vertical_datum)) {
+        Some(target) => target,
+        None => return Ok(None)
+    };
+    for dataset in datasets {
+        if let Some(vertical_datum) = dataset.vertical_datum {
+            if !geoids.can_convert(vertical_datum, target) {
+                return Err(format!("Datum conversion from {} to {} not available.", vertical_datum.as_str(), target.as_str()));
+            }
+        }
+    }
+    Ok(Some(target))
+}
+
+/// Resolves a dataset path stored in geo_data into the path GDAL opens.
+///
+/// # Arguments
+/// * `datadir` - Directory containing geospatial data.
+/// * `path` - Dataset path relative to the data directory, optionally behind an archive prefix.
+///
+/// # Returns
+/// The path of the dataset, with the archive prefix in front of the data directory if present.
+pub fn dataset_path(datadir: &Path, path: &str) -> PathBuf {
+    if VSI_REMOTE_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+        return PathBuf::from(path);
+    }
+    for prefix in VSI_ARCHIVE_PREFIXES {
+        if let Some(archive_path) = path.strip_prefix(prefix) {
+            return PathBuf::from(format!("{}{}", prefix, datadir.join(archive_path).display()));
+        }
+    }
+    datadir.join(path)
+}
+
+/// Converts a URI of a remote dataset into the path GDAL opens.
+///
+/// # Arguments
+/// * `uri` - An `http(s)://`, `s3://`, `gs://` or `az://` URI, or a path with a remote GDAL prefix.
+///
+/// # Returns
+/// The GDAL path, or `None` if the URI has an unsupported scheme.
+pub fn remote_dataset_path(uri: &str) -> Option<String> {
+    if VSI_REMOTE_PREFIXES.iter().any(|prefix| uri.starts_with(prefix)) {
+        Some(uri.to_string())
+    } else if uri.starts_with("http://") || uri.starts_with("https://") {
+        Some(format!("/vsicurl/{}", uri))
+    } else if let Some(object) = uri.strip_prefix("s3://") {
+        Some(format!("/vsis3/{}", object))
+    } else if let Some(object) = uri.strip_prefix("gs://") {
+        Some(format!("/vsigs/{}", object))
+    } else {
+        uri.strip_prefix("az://").map(|object| format!("/vsiaz/{}", object))
+    }
+}
+
+/// Configures GDAL for remote datasets.
+///
+/// Datasets are opened per lookup, so their decoded blocks do not outlive it. Repeated lookups on
+/// a Cloud Optimized GeoTIFF are served by the process wide `/vsicurl/` cache of downloaded regions
+/// and file properties instead, which saves the requests for headers and already fetched tiles.
+///
+/// The GDAL block cache size is read from `$BLOCKCACHESIZE` in megabytes, the GDAL default applies
+/// if it is not set. Options already set in the environment take precedence.
+///
+/// # Returns
+/// * `Ok(())` if the options were set.
+/// * `Err(String)` if `$BLOCKCACHESIZE` is invalid or an option could not be set.
+pub fn configure_remote_access() -> Result<(), String> {
+    let block_cache_size = match std::env::var("BLOCKCACHESIZE") {
+        Ok(block_cache_size) => match block_cache_size.parse::<u64>() {
+            Ok(block_cache_size) => Some(block_cache_size.to_string()),
+            Err(_) => return Err("Invalid value for BLOCKCACHESIZE".to_string())
+        },
+        Err(_) => None
+    };
+    let mut options = vec![
+        ("CPL_VSIL_CURL_CACHE_SIZE", REMOTE_REGION_CACHE_SIZE),
+        ("VSI_CACHE", "TRUE"),
+        ("VSI_CACHE_SIZE", "67108864"),
+        ("GDAL_DISABLE_READDIR_ON_OPEN", "EMPTY_DIR"),
+        ("GDAL_HTTP_MULTIRANGE", "YES"),
+        ("GDAL_HTTP_MERGE_CONSECUTIVE_RANGES", "YES"),
+    ];
+    if let Some(block_cache_size) = &block_cache_size {
+        options.push(("GDAL_CACHEMAX", block_cache_size.as_str()));
+    }
+    for (key, value) in options {
+        if std::env::var(key).is_ok() {
+            continue;
+        }
+        if let Err(e) = gdal::config::set_config_option(key, value) {
+            return Err(format!("GDAL option {} could not be set: {}", key, e));
+        }
+    }
+    Ok(())
+}
+
+/// Reads the EPSG code the WKT of a coordinate system ends with.
+///
+/// # Arguments
+/// * `projection` - WKT of the coordinate system.
+///
+/// # Returns
+/// The EPSG code, or `None` if the WKT is empty or does not end with one.
+pub fn epsg_code(projection: &str) -> Option<i32> {
+    projection.split("EPSG\",").last()
+        .and_then(|epsg_string| epsg_string.get(1..epsg_string.len().saturating_sub(3)))
+        .and_then(|epsg_string| epsg_string.parse::<i32>().ok())
+}
+
+/// Creates a spatial reference from an EPSG code, taking longitudes (eastings) as first axis.
+///
+/// # Arguments
+/// * `epsg_code` - The EPSG code of the coordinate system.
+///
+/// # Returns
+/// * `Ok(SpatialRef)` using the traditional GIS axis order.
+/// * `Err(String)` if the code is unknown.
+pub fn epsg_spatial_ref(epsg_code: u32) -> Result<SpatialRef, String> {
+    let mut spatial_ref = match SpatialRef::from_epsg(epsg_code) {
+        Ok(spatial_ref) => spatial_ref,
+        Err(e) => return Err(format!("SpatialRef EPSG:{} could not be parsed: {}", epsg_code, e))
+    };
+    spatial_ref.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+    Ok(spatial_ref)
+}
+
+/// Creates a transformation from EPSG 4326 (lon/lat order) into the coordinate system of a dataset.
+///
+/// # Arguments
+/// * `dataset` - The dataset whose coordinate system is the target.
+///
+/// # Returns
+/// * `Ok(CoordTransform)` taking longitudes as x and latitudes as y.
+/// * `Err(String)` if one of the spatial references could not be created.
+pub fn wgs84_to_dataset(dataset: &Dataset) -> Result<CoordTransform, String> {
+    let spat_point = epsg_spatial_ref(4326)?;
+    let mut spat_data = match SpatialRef::from_esri(&dataset.projection()) {
+        Ok(spatial_ref) => spatial_ref,
+        Err(e) => return Err(format!("Target SpatialRef could not be parsed: {}", e))
+    };
+    spat_data.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+    match CoordTransform::new(&spat_point, &spat_data) {
+        Ok(transform) => Ok(transform),
+        Err(e) => Err(format!("CoordTransform could not be created: {}", e))
+    }
+}
+
+/// Fills the empty values of a list of coordinates from a single dataset.
+///
+/// Values that are already set are left untouched, so calling this for datasets
+/// in order of preference mosaics them with the preferred dataset on top.
+///
+/// # Arguments
+/// * `values` - The values to fill, one per coordinate.
+/// * `coordinates` - Longitude and latitude of every value.
+/// * `path` - Full path of the dataset.
+/// * `band_index` - Index of the band holding the elevation, starting at 1.
+/// * `conversion` - Geoid grids with the datum of the dataset and that of the values if they differ.
+///
+/// # Returns
+/// * `Ok(usize)` with the number of values that were filled.
+/// * `Err(String)` if the dataset could not be read.
+fn fill_from_dataset(values: &mut [Option<f64>], coordinates: &[(f64, f64)], path: &Path, band_index: usize, conversion: Option<(&GeoidModels, VerticalDatum, VerticalDatum)>) -> Result<usize, String> {
+    let dataset = match Dataset::open(path) {
+        Ok(dataset) => dataset,
+        Err(e) => return Err(format!("Dataset could not be opened: {}", e))
+    };
+    let transform = wgs84_to_dataset(&dataset)?;
+    let inverse = match dataset.geo_transform().and_then(|geotransform| geotransform.invert()) {
+        Ok(inverse) => inverse,
+        Err(e) => return Err(format!("Geo transform could not be inverted: {}", e))
+    };
+    let (raster_width, raster_height) = dataset.raster_size();
+    let empty_values: Vec<usize> = (0..values.len()).filter(|index| values[*index].is_none()).collect();
+    if empty_values.is_empty() {
+        return Ok(0);
+    }
+    let mut x_coord: Vec<f64> = Vec::with_capacity(empty_values.len());
+    let mut y_coord: Vec<f64> = Vec::with_capacity(empty_values.len());
+    for index in &empty_values {
+        let (lon, lat) = coordinates[*index];
+        x_coord.push(lon);
+        y_coord.push(lat);
+    }
+    let mut z_coord = vec![0f64; empty_values.len()];
+    if let Err(e) = transform.transform_coords(&mut x_coord, &mut y_coord, &mut z_coord) {
+        return Err(format!("Transform coords could not be converted: {}", e));
+    }
+
+    let mut pixels: Vec<(usize, usize, usize)> = Vec::new();
+    for (position, index) in empty_values.iter().enumerate() {
+        let (pixel_x, pixel_y) = inverse.apply(x_coord[position], y_coord[position]);
+        if pixel_x < 0f64 || pixel_y < 0f64 || pixel_x >= raster_width as f64 || pixel_y >= raster_height as f64 {
+            continue;
+        }
+        pixels.push((*index, pixel_x as usize, pixel_y as usize));
+    }
+    if pixels.is_empty() {
+        return Ok(0);
+    }
+    let window_x = pixels.iter().map(|pixel| pixel.1).min().unwrap_or(0);
+    let window_y = pixels.iter().map(|pixel| pixel.2).min().unwrap_or(0);
+    let window_width = pixels.iter().map(|pixel| pixel.1).max().unwrap_or(0) - window_x + 1;
+    let window_height = pixels.iter().map(|pixel| pixel.2).max().unwrap_or(0) - window_y + 1;
+    // Large windows are read decimated so a coarse grid over a high resolution tile stays cheap.
+    let step = (window_width.max(window_height) as f64 / MAX_WINDOW_SIZE as f64).ceil().max(1f64) as usize;
+    let shape = (window_width.div_ceil(step), window_height.div_ceil(step));
+
+    let rasterband = match dataset.rasterband(band_index) {
+        Ok(rasterband) => rasterband,
+        Err(e) => return Err(format!("Rasterband {} could not be opened: {}", band_index, e))
+    };
+    let no_data = rasterband.no_data_value();
+    let buffer = match rasterband.read_as::<f64>((window_x as isize, window_y as isize), (window_width, window_height), shape, None) {
+        Ok(buffer) => buffer,
+        Err(e) => return Err(format!("Raster could not be read: {}", e))
+    };
+    let mut filled = 0;
+    for (index, pixel_x, pixel_y) in pixels {
+        let column = ((pixel_x - window_x) / step).min(shape.0 - 1);
+        let row = ((pixel_y - window_y) / step).min(shape.1 - 1);
+        let value = buffer.data()[row * shape.0 + column];
+        if is_no_data(value, no_data) {
+            continue;
+        }
+        let value = match conversion {
+            Some((geoids, from, to)) => {
+                let (lon, lat) = coordinates[index];
+                // Points the geoid grid does not cover stay empty for the next dataset.
+                match geoids.convert(value, lat, lon, from, to) {
+                    Ok(value) => value,
+                    Err(_) => continue
+                }
+            },
+            None => value
+        };
+        values[index] = Some(value);
+        filled += 1;
+    }
+    Ok(filled)
+}
+
+/// Reads the elevation at a list of coordinates from all datasets of a layer intersecting an area.
+///
+/// Elevations of datasets in another vertical datum are converted into the datum of the result.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection and geoid grids.
+/// * `area` - Geometry in EPSG 4326 containing all coordinates.
+/// * `layer` - Layer of the datasets.
+/// * `datum` - Requested vertical datum, that of the preferred dataset if not set.
+/// * `coordinates` - Longitude and latitude of every sample.
+///
+/// # Returns
+/// * `Ok((Vec<Option<f64>>, Option<VerticalDatum>))` with the elevation of every coordinate, `None` where no
+///   dataset provides a value, and the datum of the elevations.
+/// * `Err(String)` if the datasets could not be queried or converted.
+pub async fn sample_elevations(appstate: &AppState, area: &Geometry<f64>, layer: &str, datum: Option<VerticalDatum>, coordinates: Vec<(f64, f64)>) -> Result<(Vec<Option<f64>>, Option<VerticalDatum>), String> {
+    let datasets = source_datasets(appstate, area, layer).await?;
+    let target = target_datum(&appstate.geoids, &datasets, datum)?;
+    let geoids = appstate.geoids.clone();
+    let task = tokio::task::spawn_blocking(move || {
+        let mut values: Vec<Option<f64>> = vec![None; coordinates.len()];
+        for dataset in datasets {
+            let conversion = match (dataset.vertical_datum, target) {
+                (Some(from), Some(to)) if from != to => Some((&geoids, from, to)),
+                (Some(_), _) | (None, None) => None,
+                (None, Some(_)) => {eprintln!("{}: Unknown vertical datum", dataset.path.display()); continue}
+            };
+            match fill_from_dataset(&mut values, &coordinates, &dataset.path, dataset.band_index, conversion) {
+                Ok(_) => {},
+                Err(e) => {eprintln!("{}: {}", dataset.path.display(), e); continue}
+            };
+            if values.iter().all(Option::is_some) {
+                break;
+            }
+        }
+        values
+    });
+    match task.await {
+        Ok(values) => Ok((values, target)),
+        Err(e) => {eprintln!("{:?}", e); Err("Internal Server Error".to_string())}
+    }
+}
+
+/// Builds an elevation grid over the bounding box of an area from all datasets of a layer intersecting the area.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection and geoid grids.
+/// * `area` - Polygon in EPSG 4326.
+/// * `layer` - Layer of the datasets.
+/// * `datum` - Requested vertical datum, that of the preferred dataset if not set.
+/// * `width` - Number of grid columns.
+/// * `height` - Number of grid rows.
+///
+/// # Returns
+/// * `Ok(ElevationGrid)` with every cell covered by a dataset filled.
+/// * `Err(String)` if the datasets could not be queried or converted.
+pub async fn mosaic_grid(appstate: &AppState, area: &Polygon<f64>, layer: &str, datum: Option<VerticalDatum>, width: usize, height: usize) -> Result<ElevationGrid, String> {
+    let bbox = match area.bounding_rect() {
+        Some(bbox) => bbox,
+        None => return Err("Area is empty".to_string())
+    };
+    let mut grid = ElevationGrid::new(bbox, width, height);
+    let coordinates: Vec<(f64, f64)> = (0..width * height).map(|index| grid.cell_center(index % width, index / width)).collect();
+    (grid.values, grid.vertical_datum) = sample_elevations(appstate, &Geometry::from(area.clone()), layer, datum, coordinates).await?;
+    Ok(grid)
+}
+
+/// Encodes a dataset into a file format with a GDAL driver.
+///
+/// # Arguments
+/// * `dataset` - The dataset to encode.
+/// * `driver_name` - Short name of the output driver, e.g. `GTiff`, `COG` or `PNG`.
+/// * `options` - Creation options of the output driver.
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` with the encoded file.
+/// * `Err(String)` if GDAL could not write the file.
+pub fn encode_dataset(dataset: &Dataset, driver_name: &str, options: &RasterCreationOptions) -> Result<Vec<u8>, String> {
+    let driver = DriverManager::get_driver_by_name(driver_name).map_err(|e| e.to_string())?;
+    let mem_path = format!("/vsimem/faster-elevation-{}", MEM_FILE_COUNTER.fetch_add(1, Ordering::Relaxed));
+    // The copy has to be closed before its bytes are complete.
+    let bytes = dataset.create_copy(&driver, &mem_path, options).map(drop)
+        .and_then(|_| gdal::vsi::get_vsi_mem_file_bytes_owned(&mem_path));
+    // Drivers like PNG store the georeferencing in an .aux.xml sidecar, which would stay in memory.
+    let _ = gdal::vsi::unlink_mem_file(format!("{}.aux.xml", mem_path));
+    if bytes.is_err() {
+        let _ = gdal::vsi::unlink_mem_file(&mem_path);
+    }
+    bytes.map_err(|e| e.to_string())
+}
+
+/// Encodes a single band raster in EPSG 4326 with a GDAL driver.
+///
+/// # Arguments
+/// * `values` - Cell values row by row.
+/// * `width` - Number of columns.
+/// * `height` - Number of rows.
+/// * `geo_transform` - Geotransform of the raster.
+/// * `no_data` - Optional NoData value of the band.
+/// * `driver_name` - Short name of the output driver, e.g. `GTiff` or `PNG`.
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` with the encoded file.
+/// * `Err(String)` if GDAL could not write the raster.
+pub fn encode_raster<T: GdalType + Copy>(values: Vec<T>, width: usize, height: usize, geo_transform: GeoTransform, no_data: Option<f64>, driver_name: &str) -> Result<Vec<u8>, String> {
+    let mem_driver = DriverManager::get_driver_by_name("MEM").map_err(|e| e.to_string())?;
+    let mut dataset = mem_driver.create_with_band_type::<T, _>("", width, height, 1).map_err(|e| e.to_string())?;
+    dataset.set_geo_transform(&geo_transform).map_err(|e| e.to_string())?;
+    dataset.set_spatial_ref(&epsg_spatial_ref(4326)?).map_err(|e| e.to_string())?;
+    let mut rasterband = dataset.rasterband(1).map_err(|e| e.to_string())?;
+    rasterband.set_no_data_value(no_data).map_err(|e| e.to_string())?;
+    rasterband.write((0, 0), (width, height), &mut Buffer::new((width, height), values)).map_err(|e| e.to_string())?;
+    encode_dataset(&dataset, driver_name, &RasterCreationOptions::new())
+}
@@ -0,0 +1,340 @@
+use gdal::Dataset;
+use gdal::raster::GdalDataType;
+use gdal::spatial_ref::{AxisMappingStrategy, CoordTransform, SpatialRef};
+use geo::{coord, Intersects, Rect};
+use geozero::wkb;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use crate::ConnectionPool;
+use crate::ingest::{file_datasets, ground_resolution, is_indexable};
+use crate::raster::{dataset_path, epsg_code, epsg_spatial_ref};
+
+/// Smallest plausible ground resolution in metres.
+const MIN_GROUND_RESOLUTION: f64 = 0.01;
+/// Largest plausible ground resolution in metres.
+const MAX_GROUND_RESOLUTION: f64 = 10_000.0;
+/// Number of points per edge used to transform the footprint.
+const FOOTPRINT_DENSIFY_POINTS: i32 = 21;
+/// Number of overlapping datasets named in a check message.
+const MAX_LISTED_OVERLAPS: usize = 5;
+
+/// Outcome of a single check.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    /// The dataset can be indexed but may give unexpected results.
+    Warning,
+    /// The dataset cannot be indexed or read.
+    Error,
+}
+
+impl CheckStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warning => "warning",
+            CheckStatus::Error => "error",
+        }
+    }
+}
+
+/// Result of one check of a dataset.
+#[derive(Serialize)]
+pub struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    message: String,
+}
+
+/// Results of all checks of a dataset.
+#[derive(Serialize)]
+pub struct DatasetValidation {
+    path: String,
+    checks: Vec<Check>,
+    /// Footprint in EPSG 4326, used to check overlaps.
+    #[serde(skip)]
+    footprint: Option<Rect<f64>>,
+}
+
+impl DatasetValidation {
+    fn new(path: String) -> DatasetValidation {
+        DatasetValidation {path, checks: Vec::new(), footprint: None}
+    }
+
+    fn check(&mut self, name: &'static str, status: CheckStatus, message: String) {
+        self.checks.push(Check {name, status, message});
+    }
+
+    fn has_status(&self, status: CheckStatus) -> bool {
+        self.checks.iter().any(|check| check.status == status)
+    }
+
+    /// Returns the messages of all failed checks.
+    pub fn errors(&self) -> Vec<String> {
+        self.checks.iter().filter(|check| check.status == CheckStatus::Error).map(|check| check.message.clone()).collect()
+    }
+}
+
+/// Report of a validation run.
+#[derive(Serialize)]
+pub struct ValidationReport {
+    /// Number of datasets with at least one error.
+    pub errors: usize,
+    /// Number of datasets with warnings but no errors.
+    pub warnings: usize,
+    /// Limitations of the run, e.g. checks that were skipped.
+    pub notes: Vec<String>,
+    pub datasets: Vec<DatasetValidation>,
+}
+
+impl ValidationReport {
+    /// Prints the checks of every dataset followed by a summary.
+    pub fn print(&self) {
+        for dataset in &self.datasets {
+            println!("{}", dataset.path);
+            for check in &dataset.checks {
+                println!("  [{}] {}: {}", check.status.as_str(), check.name, check.message);
+            }
+        }
+        for note in &self.notes {
+            println!("Note: {}", note);
+        }
+        println!("{} datasets checked, {} with errors, {} with warnings", self.datasets.len(), self.errors, self.warnings);
+    }
+}
+
+/// Checks a single raster for everything indexing and lookups rely on.
+///
+/// # Arguments
+/// * `path` - Full path of the raster.
+///
+/// # Returns
+/// The results of all checks that could be run, checks depending on a failed one are left out.
+pub fn validate_dataset(path: &Path) -> DatasetValidation {
+    let mut validation = DatasetValidation::new(path.to_string_lossy().to_string());
+    let dataset = match Dataset::open(path) {
+        Ok(dataset) => dataset,
+        Err(e) => {
+            validation.check("open", CheckStatus::Error, format!("Dataset could not be opened: {}", e));
+            return validation;
+        }
+    };
+
+    let projection = dataset.projection();
+    let mut spatial_ref = match SpatialRef::from_esri(&projection) {
+        Ok(spatial_ref) if !projection.is_empty() => spatial_ref,
+        Ok(_) => {
+            validation.check("crs", CheckStatus::Error, "Dataset has no coordinate system.".to_string());
+            return validation;
+        }
+        Err(e) => {
+            validation.check("crs", CheckStatus::Error, format!("Coordinate system could not be parsed: {}", e));
+            return validation;
+        }
+    };
+    // Indexing reads the EPSG code from the end of the WKT.
+    match (epsg_code(&projection), spatial_ref.name()) {
+        (Some(epsg_number), Ok(name)) => validation.check("crs", CheckStatus::Ok, format!("EPSG:{} {}", epsg_number, name)),
+        (Some(epsg_number), Err(_)) => validation.check("crs", CheckStatus::Ok, format!("EPSG:{}", epsg_number)),
+        (None, _) => validation.check("crs", CheckStatus::Error, "Coordinate system has no EPSG code, the dataset cannot be indexed.".to_string())
+    };
+
+    let geotransform = match dataset.geo_transform() {
+        Ok(geotransform) => geotransform,
+        Err(e) => {
+            validation.check("geotransform", CheckStatus::Error, format!("Dataset has no geotransform: {}", e));
+            return validation;
+        }
+    };
+    if geotransform[1] == 0f64 || geotransform[5] == 0f64 || !geotransform.iter().all(|value| value.is_finite()) {
+        validation.check("geotransform", CheckStatus::Error, format!("Geotransform {:?} has no valid pixel size.", geotransform));
+        return validation;
+    } else if geotransform[2] != 0f64 || geotransform[4] != 0f64 {
+        validation.check("geotransform", CheckStatus::Warning, "Geotransform is rotated, footprints ignore the rotation.".to_string());
+    } else if geotransform[1] < 0f64 || geotransform[5] > 0f64 {
+        validation.check("geotransform", CheckStatus::Warning, "Raster is not north up.".to_string());
+    } else {
+        validation.check("geotransform", CheckStatus::Ok, format!("Pixel size {} x {}", geotransform[1], -geotransform[5]));
+    }
+
+    match dataset.rasterband(1) {
+        Ok(rasterband) => {
+            match rasterband.no_data_value() {
+                Some(no_data) => validation.check("nodata", CheckStatus::Ok, format!("NoData value {}", no_data)),
+                None => validation.check("nodata", CheckStatus::Warning, "No NoData value defined, gaps are read as elevations.".to_string())
+            };
+            let band_type = rasterband.band_type();
+            match band_type {
+                GdalDataType::Unknown => validation.check("data type", CheckStatus::Error, "Data type is not supported.".to_string()),
+                _ if band_type.bits() <= 8 => validation.check("data type", CheckStatus::Warning, format!("{} cannot hold the elevation range of most terrain.", band_type.name())),
+                _ => validation.check("data type", CheckStatus::Ok, band_type.name())
+            };
+        },
+        Err(e) => {
+            validation.check("bands", CheckStatus::Error, format!("Dataset has no readable band: {}", e));
+            return validation;
+        }
+    };
+
+    spatial_ref.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+    let wgs84 = match epsg_spatial_ref(4326) {
+        Ok(wgs84) => wgs84,
+        Err(e) => {
+            validation.check("footprint", CheckStatus::Error, e);
+            return validation;
+        }
+    };
+    let (width, height) = dataset.raster_size();
+    let corner_x = [geotransform[0], geotransform[0] + width as f64 * geotransform[1] + height as f64 * geotransform[2]];
+    let corner_y = [geotransform[3], geotransform[3] + width as f64 * geotransform[4] + height as f64 * geotransform[5]];
+    let bounds = [corner_x[0].min(corner_x[1]), corner_y[0].min(corner_y[1]), corner_x[0].max(corner_x[1]), corner_y[0].max(corner_y[1])];
+    let footprint = match CoordTransform::new(&spatial_ref, &wgs84).and_then(|transform| transform.transform_bounds(&bounds, FOOTPRINT_DENSIFY_POINTS)) {
+        Ok(footprint) => footprint,
+        Err(e) => {
+            validation.check("footprint", CheckStatus::Error, format!("Footprint could not be transformed to EPSG 4326: {}", e));
+            return validation;
+        }
+    };
+    let inside_world = footprint.iter().all(|value| value.is_finite())
+        && footprint[0] >= -180f64 && footprint[2] <= 180f64 && footprint[1] >= -90f64 && footprint[3] <= 90f64;
+    let resolution = ground_resolution(&spatial_ref, &geotransform, (footprint[1] + footprint[3]) / 2f64);
+    if !inside_world || footprint[0] >= footprint[2] || footprint[1] >= footprint[3] {
+        validation.check("footprint", CheckStatus::Error, format!("Footprint {:?} is outside of the world or empty.", footprint));
+        return validation;
+    } else if !(MIN_GROUND_RESOLUTION..=MAX_GROUND_RESOLUTION).contains(&resolution) {
+        validation.check("footprint", CheckStatus::Warning, format!("Ground resolution of {:.3} m is implausible, check the coordinate system.", resolution));
+    } else {
+        validation.check("footprint", CheckStatus::Ok, format!("{:.6}, {:.6} to {:.6}, {:.6}, ground resolution {:.3} m", footprint[0], footprint[1], footprint[2], footprint[3], resolution));
+    }
+    validation.footprint = Some(Rect::new(coord! {x: footprint[0], y: footprint[1]}, coord! {x: footprint[2], y: footprint[3]}));
+    validation
+}
+
+/// Validates every dataset indexing would read below a path: rasters, compressed rasters,
+/// archive members, VRTs and the datasets of remote dataset lists.
+///
+/// # Arguments
+/// * `path` - A file or a directory of files.
+///
+/// # Returns
+/// The checks of every dataset, files whose datasets could not be listed are reported as errors.
+fn validate_files(path: &Path) -> Vec<DatasetValidation> {
+    // Files are listed relative to a root, like the data directory during indexing.
+    let (root, files): (PathBuf, Vec<PathBuf>) = if path.is_file() {
+        (path.parent().unwrap_or(Path::new("")).to_path_buf(), path.file_name().map(PathBuf::from).into_iter().collect())
+    } else {
+        let files = WalkDir::new(path).into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && is_indexable(entry.path()))
+            .filter_map(|entry| entry.path().strip_prefix(path).ok().map(Path::to_path_buf))
+            .collect();
+        (path.to_path_buf(), files)
+    };
+    let mut validations = Vec::new();
+    for file in files {
+        let file_path = root.join(&file).to_string_lossy().to_string();
+        if !is_indexable(&file) {
+            let mut validation = DatasetValidation::new(file_path);
+            validation.check("open", CheckStatus::Error, "File type is not indexed.".to_string());
+            validations.push(validation);
+            continue;
+        }
+        match file_datasets(&root, &file) {
+            Ok(datasets) if datasets.is_empty() => {
+                let mut validation = DatasetValidation::new(file_path);
+                validation.check("open", CheckStatus::Warning, "File contains no raster datasets.".to_string());
+                validations.push(validation);
+            },
+            Ok(datasets) => validations.extend(datasets.iter().map(|dataset| validate_dataset(&dataset_path(&root, dataset)))),
+            Err(e) => {
+                let mut validation = DatasetValidation::new(file_path);
+                validation.check("open", CheckStatus::Error, e);
+                validations.push(validation);
+            }
+        };
+    }
+    validations
+}
+
+/// Adds the overlap check of a dataset against the indexed datasets and the rest of the delivery.
+///
+/// geo_data is only read.
+///
+/// # Arguments
+/// * `validation` - The validated dataset with its footprint.
+/// * `delivery` - Paths and footprints of all validated datasets.
+/// * `conn` - Connection to the PostgreSQL database, indexed datasets are not checked without it.
+async fn check_overlaps(validation: &mut DatasetValidation, delivery: &[(String, Rect<f64>)], conn: Option<&tokio_postgres::Client>) {
+    let footprint = match validation.footprint {
+        Some(footprint) => footprint,
+        None => return
+    };
+    let mut overlaps: Vec<String> = delivery.iter()
+        .filter(|(path, other)| *path != validation.path && other.intersects(&footprint))
+        .map(|(path, _)| path.clone())
+        .collect();
+    if let Some(conn) = conn {
+        let encoded = wkb::Encode(geo::Geometry::Polygon(footprint.to_polygon()));
+        match conn.query("SELECT path FROM geo_data WHERE ST_Intersects(object, ST_SetSRID(CAST ($1 AS geometry),4326)) ORDER BY path", &[&encoded]).await {
+            Ok(rows) => overlaps.extend(rows.iter().map(|row| row.get::<_, String>("path"))),
+            Err(e) => {
+                validation.check("overlap", CheckStatus::Warning, format!("Indexed datasets could not be checked: {}", e));
+                return;
+            }
+        };
+    }
+    if overlaps.is_empty() {
+        validation.check("overlap", CheckStatus::Ok, "No overlapping datasets.".to_string());
+    } else {
+        let listed: Vec<&str> = overlaps.iter().take(MAX_LISTED_OVERLAPS).map(|path| path.as_str()).collect();
+        let more = if overlaps.len() > MAX_LISTED_OVERLAPS { format!(" and {} more", overlaps.len() - MAX_LISTED_OVERLAPS) } else { String::new() };
+        validation.check("overlap", CheckStatus::Warning, format!("Overlaps {} datasets: {}{}", overlaps.len(), listed.join(", "), more));
+    }
+}
+
+/// Validates every dataset below a path without changing geo_data.
+///
+/// # Arguments
+/// * `path` - A file or a directory of files.
+/// * `pool` - The connection pool to the PostgreSQL database, used to find overlapping indexed datasets.
+///   Without it only overlaps within the delivery are checked.
+///
+/// # Returns
+/// * `Ok(ValidationReport)` with the checks of every dataset.
+/// * `Err(String)` if the path does not exist.
+pub async fn validate_path(path: &Path, pool: Option<&ConnectionPool>) -> Result<ValidationReport, String> {
+    if !path.exists() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+    let mut notes: Vec<String> = Vec::new();
+    // One connection serves all overlap checks, so an unreachable database is only waited for once.
+    let conn = match pool {
+        Some(pool) => match pool.get().await {
+            Ok(conn) => Some(conn),
+            Err(e) => {
+                notes.push(format!("Database Connection could not be established, overlaps with indexed datasets were not checked: {}", e));
+                None
+            }
+        },
+        None => {
+            notes.push("No database configured, overlaps with indexed datasets were not checked.".to_string());
+            None
+        }
+    };
+    let validation_path = path.to_path_buf();
+    let mut datasets = match tokio::task::spawn_blocking(move || validate_files(&validation_path)).await {
+        Ok(datasets) => datasets,
+        Err(e) => return Err(format!("Datasets could not be validated: {}", e))
+    };
+    let delivery: Vec<(String, Rect<f64>)> = datasets.iter()
+        .filter_map(|dataset| dataset.footprint.map(|footprint| (dataset.path.clone(), footprint)))
+        .collect();
+    for dataset in datasets.iter_mut() {
+        check_overlaps(dataset, &delivery, conn.as_deref()).await;
+    }
+    let errors = datasets.iter().filter(|dataset| dataset.has_status(CheckStatus::Error)).count();
+    let warnings = datasets.iter().filter(|dataset| !dataset.has_status(CheckStatus::Error) && dataset.has_status(CheckStatus::Warning)).count();
+    Ok(ValidationReport {errors, warnings, notes, datasets})
+}
@@ -0,0 +1,253 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+    extract::{State},
+};
+use axum_macros::debug_handler;
+use geo::{coord, Rect};
+use serde::Deserialize;
+use serde_json::json;
+use crate::{AppState, error_response};
+use crate::datum::VerticalDatum;
+use crate::line_of_sight::{DEFAULT_K_FACTOR, EARTH_RADIUS};
+use crate::raster::{encode_raster, mosaic_grid, ElevationGrid, METRES_PER_DEGREE};
+/// Cell size in metres used when the request does not specify one.
+const DEFAULT_RESOLUTION: f64 = 30.0;
+/// Upper bound for the number of cells per axis of a viewshed.
+const MAX_GRID_SIZE: usize = 2001;
+/// Mask value of visible cells in raster output.
+const MASK_VISIBLE: u8 = 1;
+/// Mask value of hidden cells in raster output.
+const MASK_HIDDEN: u8 = 0;
+/// Mask value of cells outside the radius or without elevation data.
+const MASK_NO_DATA: u8 = 255;
+
+#[derive(Deserialize)]
+pub struct PostViewshed {
+    latitude: f64,
+    longitude: f64,
+    /// Observer height above ground in metres.
+    #[serde(default)]
+    height: f64,
+    /// Radius of the viewshed in metres.
+    radius: f64,
+    /// Cell size of the viewshed in metres.
+    resolution: Option<f64>,
+    /// Height above ground of the observed targets in metres.
+    #[serde(default)]
+    target_height: f64,
+    /// Whether the earth bulge lowers distant terrain.
+    #[serde(default)]
+    earth_curvature: bool,
+    /// Effective earth radius factor used together with `earth_curvature`.
+    k_factor: Option<f64>,
+    /// Output format, one of `geojson`, `geotiff` or `png`.
+    format: Option<String>,
+    /// Layer the terrain is read from, `$DEFAULTLAYER` if not set.
+    layer: Option<String>,
+    /// Vertical datum of the observer elevation, that of the preferred dataset if not set.
+    datum: Option<VerticalDatum>,
+}
+
+/// Computes which cells of a grid are visible from the observer cell.
+///
+/// Rays are cast from the observer to every cell on the border of the grid; a cell is
+/// visible if its elevation angle is not below the highest angle seen before it on the ray.
+///
+/// # Arguments
+/// * `grid` - Elevation grid with the observer in its centre cell.
+/// * `request` - The viewshed request holding heights and curvature options.
+/// * `resolution` - Cell size in metres.
+/// * `observer_elevation` - Terrain elevation below the observer.
+///
+/// # Returns
+/// One mask value per grid cell.
+fn compute_viewshed(grid: &ElevationGrid, request: &PostViewshed, resolution: f64, observer_elevation: f64) -> Vec<u8> {
+    let center = (grid.width / 2) as i64;
+    let size = grid.width as i64;
+    let k_factor = request.k_factor.unwrap_or(DEFAULT_K_FACTOR);
+    let observer_height = observer_elevation + request.height;
+    let mut mask = vec![MASK_NO_DATA; grid.width * grid.height];
+    mask[(center * size + center) as usize] = MASK_VISIBLE;
+
+    let mut border: Vec<(i64, i64)> = Vec::new();
+    for index in 0..size {
+        border.push((index, 0));
+        border.push((index, size - 1));
+        border.push((0, index));
+        border.push((size - 1, index));
+    }
+    for (target_col, target_row) in border {
+        let delta_col = target_col - center;
+        let delta_row = target_row - center;
+        let steps = delta_col.abs().max(delta_row.abs());
+        let mut max_slope = f64::NEG_INFINITY;
+        for step in 1..=steps {
+            let col = center + (delta_col as f64 * step as f64 / steps as f64).round() as i64;
+            let row = center + (delta_row as f64 * step as f64 / steps as f64).round() as i64;
+            let distance = (((col - center).pow(2) + (row - center).pow(2)) as f64).sqrt() * resolution;
+            if distance > request.radius {
+                break;
+            }
+            let elevation = match grid.get(col as usize, row as usize) {
+                Some(elevation) => elevation,
+                None => continue
+            };
+            let earth_bulge = if request.earth_curvature {
+                distance * distance / (2f64 * k_factor * EARTH_RADIUS)
+            } else {
+                0f64
+            };
+            let terrain = elevation - earth_bulge;
+            let index = (row * size + col) as usize;
+            let target_slope = (terrain + request.target_height - observer_height) / distance;
+            if target_slope >= max_slope {
+                mask[index] = MASK_VISIBLE;
+            } else if mask[index] != MASK_VISIBLE {
+                mask[index] = MASK_HIDDEN;
+            }
+            max_slope = max_slope.max((terrain - observer_height) / distance);
+        }
+    }
+    mask
+}
+
+/// Converts the visible cells of a mask into a GeoJSON feature collection.
+///
+/// Consecutive visible cells of a row are merged into one rectangle.
+fn mask_to_geojson(grid: &ElevationGrid, mask: &[u8], observer_elevation: f64) -> serde_json::Value {
+    let mut polygons: Vec<serde_json::Value> = Vec::new();
+    for row in 0..grid.height {
+        let mut col = 0;
+        while col < grid.width {
+            if mask[row * grid.width + col] != MASK_VISIBLE {
+                col += 1;
+                continue;
+            }
+            let start = col;
+            while col < grid.width && mask[row * grid.width + col] == MASK_VISIBLE {
+                col += 1;
+            }
+            let west = grid.origin_lon + start as f64 * grid.pixel_lon;
+            let east = grid.origin_lon + col as f64 * grid.pixel_lon;
+            let north = grid.origin_lat - row as f64 * grid.pixel_lat;
+            let south = north - grid.pixel_lat;
+            polygons.push(json!([[[west, south], [east, south], [east, north], [west, north], [west, south]]]));
+        }
+    }
+    json!({
+        "type": "FeatureCollection",
+        "features": [{
+            "type": "Feature",
+            "geometry": {"type": "MultiPolygon", "coordinates": polygons},
+            "properties": {"observer_elevation": observer_elevation, "vertical_datum": grid.vertical_datum.map(|datum| datum.as_str())},
+        }],
+    })
+}
+
+/// Handles POST requests to compute the viewshed of an observer.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection.
+/// * `payload` - JSON payload containing the observer and the viewshed options.
+///
+/// # Returns
+/// The visible area as GeoJSON, GeoTIFF or PNG mask.
+#[debug_handler]
+pub async fn post_viewshed(
+    State(appstate): State<AppState>, Json(payload): Json<PostViewshed>) -> Response {
+    let resolution = payload.resolution.unwrap_or(DEFAULT_RESOLUTION);
+    if resolution <= 0f64 || payload.radius <= 0f64 {
+        return error_response(StatusCode::BAD_REQUEST, "radius and resolution must be positive.".to_string());
+    }
+    let format = payload.format.clone().unwrap_or_else(|| "geojson".to_string());
+    if !["geojson", "geotiff", "png"].contains(&format.as_str()) {
+        return error_response(StatusCode::BAD_REQUEST, format!("Unknown format {}.", format));
+    }
+    // Checked before casting, a cast would saturate and the grid size overflow.
+    if payload.radius / resolution > (MAX_GRID_SIZE / 2) as f64 {
+        return error_response(StatusCode::BAD_REQUEST, format!("radius / resolution must not exceed {}.", MAX_GRID_SIZE / 2));
+    }
+    let half_size = (payload.radius / resolution).ceil() as usize;
+    let size = 2 * half_size + 1;
+    let pixel_lat = resolution / METRES_PER_DEGREE;
+    // Cells get infinitely wide towards the poles.
+    if payload.latitude.abs() + (half_size as f64 + 0.5) * pixel_lat >= 90f64 {
+        return error_response(StatusCode::BAD_REQUEST, "The viewshed must not reach the poles.".to_string());
+    }
+    let pixel_lon = resolution / (METRES_PER_DEGREE * payload.latitude.to_radians().cos());
+    let bbox = Rect::new(
+        coord! {x: payload.longitude - (half_size as f64 + 0.5) * pixel_lon, y: payload.latitude - (half_size as f64 + 0.5) * pixel_lat},
+        coord! {x: payload.longitude + (half_size as f64 + 0.5) * pixel_lon, y: payload.latitude + (half_size as f64 + 0.5) * pixel_lat},
+    );
+    let layer = payload.layer.clone().unwrap_or_else(|| appstate.ingest_config.default_layer.clone());
+    let grid = match mosaic_grid(&appstate, &bbox.to_polygon(), &layer, payload.datum, size, size).await {
+        Ok(grid) => grid,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e)
+    };
+    let observer_elevation = match grid.get(half_size, half_size) {
+        Some(observer_elevation) => observer_elevation,
+        None => return error_response(StatusCode::NOT_FOUND, format!("No such coordinate {} {}.", payload.latitude, payload.longitude))
+    };
+    let mask = compute_viewshed(&grid, &payload, resolution, observer_elevation);
+    let (driver_name, content_type) = match format.as_str() {
+        "geotiff" => ("GTiff", "image/tiff"),
+        "png" => ("PNG", "image/png"),
+        _ => return (StatusCode::OK, Json(mask_to_geojson(&grid, &mask, observer_elevation))).into_response()
+    };
+    let geo_transform = grid.geo_transform();
+    let encoded = tokio::task::spawn_blocking(move || {
+        encode_raster::<u8>(mask, size, size, geo_transform, Some(MASK_NO_DATA as f64), driver_name)
+    }).await;
+    match encoded {
+        Ok(Ok(bytes)) => (StatusCode::OK, [(header::CONTENT_TYPE, content_type)], bytes).into_response(),
+        Ok(Err(e)) => {eprintln!("{}", e); error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string())}
+        Err(e) => {eprintln!("{:?}", e); error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string())}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(radius: f64) -> PostViewshed {
+        PostViewshed {
+            latitude: 0f64,
+            longitude: 0f64,
+            height: 2f64,
+            radius,
+            resolution: Some(1f64),
+            target_height: 0f64,
+            earth_curvature: false,
+            k_factor: None,
+            format: None,
+            layer: None,
+            datum: None,
+        }
+    }
+
+    /// Flat 5 x 5 grid with a wall east of the observer in the centre cell.
+    fn grid() -> ElevationGrid {
+        let mut grid = ElevationGrid::new(Rect::new(coord! {x: 0f64, y: 0f64}, coord! {x: 5f64, y: 5f64}), 5, 5);
+        grid.values = vec![Some(0f64); 25];
+        grid.values[2 * 5 + 3] = Some(100f64);
+        grid
+    }
+
+    #[test]
+    fn wall_hides_the_terrain_behind_it() {
+        let mask = compute_viewshed(&grid(), &request(10f64), 1f64, 0f64);
+        assert_eq!(mask[2 * 5 + 2], MASK_VISIBLE);
+        assert_eq!(mask[2 * 5 + 3], MASK_VISIBLE);
+        assert_eq!(mask[2 * 5 + 4], MASK_HIDDEN);
+        assert_eq!(mask[2 * 5], MASK_VISIBLE);
+    }
+
+    #[test]
+    fn cells_beyond_the_radius_have_no_data() {
+        let mask = compute_viewshed(&grid(), &request(1.5), 1f64, 0f64);
+        assert_eq!(mask[0], MASK_NO_DATA);
+        assert_eq!(mask[2 * 5 + 1], MASK_VISIBLE);
+    }
+}
@@ -0,0 +1,122 @@
+use geo::{Intersects, Point, Rect};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::event::ModifyKind;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use crate::AppState;
+use crate::ingest::{sidecar_owner, sync_file};
+
+/// Time without further events after which collected changes are applied.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Removes all cached lookups inside the given areas.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the cache.
+/// * `areas` - Bounding boxes in EPSG 4326 whose lookups changed.
+pub fn invalidate_areas(appstate: &AppState, areas: Vec<Rect<f64>>) {
+    if areas.is_empty() {
+        return;
+    }
+    let predicate = appstate.cache.invalidate_entries_if(move |_, coordinate_result| {
+        let point = Point::new(coordinate_result.longitude, coordinate_result.latitude);
+        areas.iter().any(|area| area.intersects(&point))
+    });
+    if let Err(e) = predicate {
+        eprintln!("Cache entries could not be invalidated, clearing cache: {}", e);
+        appstate.cache.invalidate_all();
+    }
+}
+
+/// Applies a set of changed files to geo_data and the lookup cache.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection, ingestion configuration and cache.
+/// * `datadir` - Canonical path of the data directory.
+/// * `paths` - Changed files and created, moved or removed directories reported by the watcher.
+async fn apply_changes(appstate: &AppState, datadir: &Path, paths: HashSet<PathBuf>) {
+    let mut changed_areas: Vec<Rect<f64>> = Vec::new();
+    for path in &paths {
+        // Files below a changed directory are synced with the directory.
+        if path.ancestors().skip(1).any(|ancestor| paths.contains(ancestor)) {
+            continue;
+        }
+        let filepath = match path.strip_prefix(datadir) {
+            Ok(filepath) if filepath.as_os_str().is_empty() => continue,
+            Ok(filepath) => filepath,
+            Err(_) => continue
+        };
+        // A changed sidecar reindexes the file it describes.
+        let owner = sidecar_owner(filepath);
+        let filepath = owner.as_deref().unwrap_or(filepath);
+        match sync_file(datadir, &appstate.db_connection, &appstate.ingest_config, filepath).await {
+            Ok(areas) => {
+                println!("Updated index for {}", filepath.display());
+                changed_areas.extend(areas);
+            },
+            Err(e) => eprintln!("{}: {}", filepath.display(), e)
+        };
+    }
+    invalidate_areas(appstate, changed_areas);
+}
+
+/// Watches the data directory and keeps geo_data and the lookup cache up to date while serving.
+///
+/// Events are collected until the directory was quiet for a moment, so files that are still
+/// being copied are indexed once they are complete.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection, data directory, ingestion configuration and cache.
+///
+/// # Returns
+/// * `Ok(RecommendedWatcher)` which must be kept alive for as long as the directory is watched.
+/// * `Err(String)` if the directory could not be watched.
+pub fn watch_datadir(appstate: AppState) -> Result<RecommendedWatcher, String> {
+    let datadir = match Path::new(&appstate.datadir).canonicalize() {
+        Ok(datadir) => datadir,
+        Err(e) => return Err(format!("Data directory could not be resolved: {}", e))
+    };
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Event>();
+    let mut watcher = match notify::recommended_watcher(move |event: notify::Result<Event>| {
+        match event {
+            Ok(event) => {let _ = sender.send(event);},
+            Err(e) => eprintln!("Watch error: {}", e)
+        };
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => return Err(format!("Watcher could not be created: {}", e))
+    };
+    if let Err(e) = watcher.watch(&datadir, RecursiveMode::Recursive) {
+        return Err(format!("Data directory could not be watched: {}", e));
+    }
+
+    tokio::spawn(async move {
+        let mut pending: HashSet<PathBuf> = HashSet::new();
+        loop {
+            let event = if pending.is_empty() {
+                receiver.recv().await
+            } else {
+                match tokio::time::timeout(DEBOUNCE, receiver.recv()).await {
+                    Ok(event) => event,
+                    Err(_) => {
+                        apply_changes(&appstate, &datadir, std::mem::take(&mut pending)).await;
+                        continue;
+                    }
+                }
+            };
+            let event = match event {
+                Some(event) => event,
+                None => break
+            };
+            // Directories only matter when they appear or vanish, files below them are not reported then.
+            match event.kind {
+                EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_)) | EventKind::Remove(_) => pending.extend(event.paths),
+                EventKind::Modify(_) => pending.extend(event.paths.into_iter().filter(|path| !path.is_dir())),
+                _ => {}
+            };
+        }
+    });
+    Ok(watcher)
+}
@@ -0,0 +1,234 @@
+use axum::{
+    http::StatusCode,
+    Json,
+    extract::{State},
+};
+use axum_macros::debug_handler;
+use geo::{BoundingRect, Contains, Geometry, Point};
+use geozero::{geojson::GeoJson, ToGeo};
+use serde::{Deserialize, Serialize};
+use crate::AppState;
+use crate::datum::VerticalDatum;
+use crate::raster::{mosaic_grid, METRES_PER_DEGREE};
+
+/// Cell size in metres used when the request does not specify one.
+const DEFAULT_RESOLUTION: f64 = 30.0;
+/// Number of histogram bins used when the request does not specify one.
+const DEFAULT_BINS: usize = 10;
+/// Upper bound for the number of histogram bins.
+const MAX_BINS: usize = 1000;
+/// Upper bound for the number of grid cells sampled for a single polygon.
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Deserialize)]
+pub struct PostZonalStatistics {
+    /// GeoJSON polygon geometry in EPSG 4326.
+    polygon: serde_json::Value,
+    /// Sampling cell size in metres.
+    resolution: Option<f64>,
+    /// Number of histogram bins.
+    bins: Option<usize>,
+    /// Layer the elevations are read from, `$DEFAULTLAYER` if not set.
+    layer: Option<String>,
+    /// Vertical datum of the statistics, that of the preferred dataset if not set.
+    datum: Option<VerticalDatum>,
+}
+
+/// Structure representing an extreme point of the polygon.
+#[derive(Serialize)]
+struct ExtremePoint {
+    longitude: f64,
+    latitude: f64,
+    elevation: f64,
+}
+
+/// Structure representing one bin of the area weighted histogram.
+#[derive(Serialize)]
+struct HistogramBin {
+    /// Lower elevation bound of the bin.
+    min: f64,
+    /// Upper elevation bound of the bin.
+    max: f64,
+    /// Area in square metres with an elevation inside the bin.
+    area: f64,
+}
+
+#[derive(Default, Serialize)]
+pub struct ZonalStatisticsResult {
+    min: Option<f64>,
+    max: Option<f64>,
+    mean: Option<f64>,
+    median: Option<f64>,
+    standard_deviation: Option<f64>,
+    /// Area in square metres covered by elevation data.
+    area: f64,
+    histogram: Vec<HistogramBin>,
+    highest: Option<ExtremePoint>,
+    lowest: Option<ExtremePoint>,
+    /// Vertical datum of the elevations.
+    vertical_datum: Option<String>,
+    /// Optional error message.
+    error: Option<String>,
+}
+
+impl ZonalStatisticsResult {
+    fn from_error(error: String) -> ZonalStatisticsResult {
+        ZonalStatisticsResult {error: Some(error), ..Default::default()}
+    }
+}
+
+/// Structure representing a sampled cell inside the polygon.
+struct Sample {
+    longitude: f64,
+    latitude: f64,
+    elevation: f64,
+    /// Area of the cell in square metres.
+    area: f64,
+}
+
+/// Computes area weighted statistics over the samples of a polygon.
+///
+/// # Arguments
+/// * `samples` - Cells inside the polygon, must not be empty.
+/// * `bins` - Number of histogram bins.
+///
+/// # Returns
+/// The statistics of the samples.
+fn compute_statistics(mut samples: Vec<Sample>, bins: usize) -> ZonalStatisticsResult {
+    samples.sort_by(|a, b| a.elevation.total_cmp(&b.elevation));
+    let lowest = &samples[0];
+    let highest = &samples[samples.len() - 1];
+    let total_area: f64 = samples.iter().map(|sample| sample.area).sum();
+    let mean = samples.iter().map(|sample| sample.elevation * sample.area).sum::<f64>() / total_area;
+    let variance = samples.iter().map(|sample| (sample.elevation - mean).powi(2) * sample.area).sum::<f64>() / total_area;
+    let mut median = lowest.elevation;
+    let mut accumulated_area = 0f64;
+    for sample in &samples {
+        accumulated_area += sample.area;
+        if accumulated_area >= total_area / 2f64 {
+            median = sample.elevation;
+            break;
+        }
+    }
+
+    let bin_width = (highest.elevation - lowest.elevation) / bins as f64;
+    let mut histogram: Vec<HistogramBin> = (0..bins)
+        .map(|bin| HistogramBin {
+            min: lowest.elevation + bin as f64 * bin_width,
+            max: lowest.elevation + (bin + 1) as f64 * bin_width,
+            area: 0f64,
+        })
+        .collect();
+    for sample in &samples {
+        let bin = if bin_width > 0f64 {
+            (((sample.elevation - lowest.elevation) / bin_width) as usize).min(bins - 1)
+        } else {
+            0
+        };
+        histogram[bin].area += sample.area;
+    }
+
+    ZonalStatisticsResult {
+        min: Some(lowest.elevation),
+        max: Some(highest.elevation),
+        mean: Some(mean),
+        median: Some(median),
+        standard_deviation: Some(variance.sqrt()),
+        area: total_area,
+        histogram,
+        highest: Some(ExtremePoint {longitude: highest.longitude, latitude: highest.latitude, elevation: highest.elevation}),
+        lowest: Some(ExtremePoint {longitude: lowest.longitude, latitude: lowest.latitude, elevation: lowest.elevation}),
+        vertical_datum: None,
+        error: None,
+    }
+}
+
+/// Handles POST requests to compute elevation statistics over a polygon.
+///
+/// # Arguments
+/// * `appstate` - Application state containing the database connection.
+/// * `payload` - JSON payload containing the polygon and the sampling options.
+///
+/// # Returns
+/// A tuple containing the status code and the JSON result.
+#[debug_handler]
+pub async fn post_zonal_statistics(
+    State(appstate): State<AppState>, Json(payload): Json<PostZonalStatistics>) -> (StatusCode, Json<ZonalStatisticsResult>) {
+    let polygon = match GeoJson(&payload.polygon.to_string()).to_geo() {
+        Ok(Geometry::Polygon(polygon)) => polygon,
+        Ok(_) => return (StatusCode::BAD_REQUEST, Json(ZonalStatisticsResult::from_error("polygon must be a GeoJSON Polygon.".to_string()))),
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ZonalStatisticsResult::from_error(format!("polygon could not be parsed: {}", e))))
+    };
+    let bbox = match polygon.bounding_rect() {
+        Some(bbox) => bbox,
+        None => return (StatusCode::BAD_REQUEST, Json(ZonalStatisticsResult::from_error("polygon must not be empty.".to_string())))
+    };
+    let resolution = payload.resolution.unwrap_or(DEFAULT_RESOLUTION);
+    let bins = payload.bins.unwrap_or(DEFAULT_BINS);
+    if resolution <= 0f64 || !(1..=MAX_BINS).contains(&bins) {
+        return (StatusCode::BAD_REQUEST, Json(ZonalStatisticsResult::from_error(format!("resolution must be positive and bins between 1 and {}.", MAX_BINS))));
+    }
+    let longitude_scale = bbox.center().y.to_radians().cos();
+    let width = ((bbox.width() * METRES_PER_DEGREE * longitude_scale) / resolution).ceil().max(1f64) as usize;
+    let height = ((bbox.height() * METRES_PER_DEGREE) / resolution).ceil().max(1f64) as usize;
+    if width.checked_mul(height).is_none_or(|cells| cells > MAX_CELLS) {
+        return (StatusCode::BAD_REQUEST, Json(ZonalStatisticsResult::from_error("polygon is too large for the requested resolution.".to_string())));
+    }
+    let layer = payload.layer.unwrap_or_else(|| appstate.ingest_config.default_layer.clone());
+    let grid = match mosaic_grid(&appstate, &polygon, &layer, payload.datum, width, height).await {
+        Ok(grid) => grid,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(ZonalStatisticsResult::from_error(e)))
+    };
+
+    let cell_height = grid.pixel_lat * METRES_PER_DEGREE;
+    let mut samples: Vec<Sample> = Vec::new();
+    for row in 0..grid.height {
+        for col in 0..grid.width {
+            let elevation = match grid.get(col, row) {
+                Some(elevation) => elevation,
+                None => continue
+            };
+            let (longitude, latitude) = grid.cell_center(col, row);
+            if !polygon.contains(&Point::new(longitude, latitude)) {
+                continue;
+            }
+            let cell_width = grid.pixel_lon * METRES_PER_DEGREE * latitude.to_radians().cos();
+            samples.push(Sample {longitude, latitude, elevation, area: cell_width * cell_height});
+        }
+    }
+    if samples.is_empty() {
+        return (StatusCode::OK, Json(ZonalStatisticsResult::from_error("No elevation data inside polygon.".to_string())));
+    }
+    let statistics = compute_statistics(samples, bins);
+    (StatusCode::OK, Json(ZonalStatisticsResult {vertical_datum: grid.vertical_datum.map(|datum| datum.as_str().to_string()), ..statistics}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(elevations: &[f64]) -> Vec<Sample> {
+        elevations.iter().map(|elevation| Sample {longitude: 0f64, latitude: 0f64, elevation: *elevation, area: 1f64}).collect()
+    }
+
+    #[test]
+    fn computes_area_weighted_statistics() {
+        let result = compute_statistics(samples(&[4f64, 2f64, 1f64, 3f64]), 3);
+        assert_eq!(result.min, Some(1f64));
+        assert_eq!(result.max, Some(4f64));
+        assert_eq!(result.mean, Some(2.5));
+        assert_eq!(result.median, Some(2f64));
+        assert_eq!(result.standard_deviation, Some(1.25f64.sqrt()));
+        assert_eq!(result.area, 4f64);
+        let areas: Vec<f64> = result.histogram.iter().map(|bin| bin.area).collect();
+        assert_eq!(areas, vec![1f64, 1f64, 2f64]);
+    }
+
+    #[test]
+    fn flat_polygon_fills_the_first_bin() {
+        let result = compute_statistics(samples(&[5f64, 5f64]), 2);
+        assert_eq!(result.standard_deviation, Some(0f64));
+        assert_eq!(result.histogram[0].area, 2f64);
+        assert_eq!(result.histogram[1].area, 0f64);
+    }
+}